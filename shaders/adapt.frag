@@ -0,0 +1,17 @@
+#version 330 core
+
+// Moves the adapted luminance part of the way towards this frame's average.
+
+out float adapted;
+
+uniform sampler2D luminance;
+uniform sampler2D previous;
+// Mip level of the log luminance texture that is a single texel
+uniform float last_level;
+// Fraction of the way to move, 1 to jump straight to the average
+uniform float blend;
+
+void main(){
+    float average = exp(textureLod(luminance, vec2(0.5), last_level).r);
+    adapted = mix(texture(previous, vec2(0.5)).r, average, blend);
+}
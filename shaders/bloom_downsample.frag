@@ -0,0 +1,48 @@
+#version 330 core
+
+// 13 tap downsample from Jimenez's Call of Duty bloom, which avoids the flickering of a plain 2x2 box.
+
+in vec2 uv;
+
+out vec3 color;
+
+uniform sampler2D source;
+// Size of one texel of the source
+uniform vec2 texel_size;
+// Keep only what is brighter than the threshold, with a soft knee so the cut-off isn't visible
+uniform bool bright_pass;
+uniform float threshold;
+
+vec3 brightPass(vec3 c){
+    float brightness = max(c.r, max(c.g, c.b));
+    float knee = 0.5 * threshold;
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    return c * max(soft, brightness - threshold) / max(brightness, 1e-4);
+}
+
+void main(){
+    vec2 t = texel_size;
+    vec3 a = texture(source, uv + t * vec2(-2.0, 2.0)).rgb;
+    vec3 b = texture(source, uv + t * vec2(0.0, 2.0)).rgb;
+    vec3 c = texture(source, uv + t * vec2(2.0, 2.0)).rgb;
+    vec3 d = texture(source, uv + t * vec2(-2.0, 0.0)).rgb;
+    vec3 e = texture(source, uv).rgb;
+    vec3 f = texture(source, uv + t * vec2(2.0, 0.0)).rgb;
+    vec3 g = texture(source, uv + t * vec2(-2.0, -2.0)).rgb;
+    vec3 h = texture(source, uv + t * vec2(0.0, -2.0)).rgb;
+    vec3 i = texture(source, uv + t * vec2(2.0, -2.0)).rgb;
+    vec3 j = texture(source, uv + t * vec2(-1.0, 1.0)).rgb;
+    vec3 k = texture(source, uv + t * vec2(1.0, 1.0)).rgb;
+    vec3 l = texture(source, uv + t * vec2(-1.0, -1.0)).rgb;
+    vec3 m = texture(source, uv + t * vec2(1.0, -1.0)).rgb;
+
+    color = e * 0.125;
+    color += (a + c + g + i) * 0.03125;
+    color += (b + d + f + h) * 0.0625;
+    color += (j + k + l + m) * 0.125;
+
+    if (bright_pass){
+        color = brightPass(color);
+    }
+}
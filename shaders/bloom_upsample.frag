@@ -0,0 +1,23 @@
+#version 330 core
+
+// 3x3 tent filter upsample, added to the next larger level of the bloom chain.
+
+in vec2 uv;
+
+out vec3 color;
+
+uniform sampler2D source;
+// Size of one texel of the source
+uniform vec2 texel_size;
+// Spread of the filter in source texels; larger values give a wider glow
+uniform float radius;
+
+void main(){
+    vec2 t = texel_size * radius;
+    color = texture(source, uv).rgb * 4.0;
+    color += (texture(source, uv + vec2(-t.x, 0.0)).rgb + texture(source, uv + vec2(t.x, 0.0)).rgb
+            + texture(source, uv + vec2(0.0, -t.y)).rgb + texture(source, uv + vec2(0.0, t.y)).rgb) * 2.0;
+    color += texture(source, uv + vec2(-t.x, -t.y)).rgb + texture(source, uv + vec2(t.x, -t.y)).rgb
+            + texture(source, uv + vec2(-t.x, t.y)).rgb + texture(source, uv + vec2(t.x, t.y)).rgb;
+    color /= 16.0;
+}
@@ -0,0 +1,69 @@
+#version 330 core
+
+// Integrates the GGX specular BRDF over the hemisphere into a scale and bias for F0, indexed by n·v along x
+// and roughness along y.
+
+in vec2 uv;
+
+out vec2 color;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+float radicalInverse(uint bits){
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint i, uint n){
+    return vec2(float(i) / float(n), radicalInverse(i));
+}
+
+vec3 importanceSampleGGX(vec2 xi, vec3 n, float roughness){
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+// Schlick-GGX with the k used for image based lighting, which differs from the one for punctual lights
+float geometrySmith(float n_dot_v, float n_dot_l, float roughness){
+    float k = roughness * roughness / 2.0;
+    float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+void main(){
+    float n_dot_v = max(uv.x, 1e-3);
+    float roughness = uv.y;
+    vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    vec3 n = vec3(0.0, 0.0, 1.0);
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for(uint i = 0u; i < SAMPLE_COUNT; i++){
+        vec3 h = importanceSampleGGX(hammersley(i, SAMPLE_COUNT), n, roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+
+        float n_dot_l = max(l.z, 0.0);
+        float n_dot_h = max(h.z, 0.0);
+        float v_dot_h = max(dot(v, h), 0.0);
+
+        if (n_dot_l > 0.0){
+            float g = geometrySmith(n_dot_v, n_dot_l, roughness);
+            float g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            float fc = pow(1.0 - v_dot_h, 5.0);
+
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+
+    color = vec2(scale, bias) / float(SAMPLE_COUNT);
+}
@@ -0,0 +1,62 @@
+#version 330 core
+
+// Lighting pass of the deferred pipeline: shades one light at a time from the G-buffer, blended additively.
+
+out vec4 color;
+
+#include "lighting.glsl"
+
+uniform sampler2D g_position;
+uniform sampler2D g_normal;
+uniform sampler2D g_albedo_ao;
+uniform sampler2D g_metallic_roughness;
+uniform sampler2D g_emissive;
+
+// 0 for directional, 1 for point and 2 for spot lights, added together. 3 writes emissive and environment lighting
+// first, over the clear colour.
+uniform int light_type;
+uniform int light_index;
+
+uniform vec2 screen_size;
+uniform vec3 camera_pos;
+
+// Screen space ambient occlusion, see src/ssao.rs
+uniform bool ssao;
+uniform sampler2D ssao_map;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / screen_size;
+    vec3 normal = texture(g_normal, uv).xyz;
+
+    // Pixels no geometry was written to keep the clear colour
+    if (dot(normal, normal) == 0.0){
+        discard;
+    }
+
+    vec3 frag_pos = texture(g_position, uv).xyz;
+    vec4 albedo_ao = texture(g_albedo_ao, uv);
+    vec3 metallic_roughness = texture(g_metallic_roughness, uv).rgb;
+    bool receive_shadows = metallic_roughness.z > 0.5;
+    Surface surface = Surface(albedo_ao.rgb, metallic_roughness.x, metallic_roughness.y, albedo_ao.a);
+    if (ssao) surface.ao *= texture(ssao_map, uv).r;
+    vec3 norm = normalize(normal);
+    vec3 view_dir = normalize(camera_pos - frag_pos);
+    vec3 res;
+
+    if (light_type == 0){
+        // Only the first directional light has a shadow map
+        float shadow = receive_shadows && light_index == 0? calculateShadow(dir_lights[light_index], frag_pos, norm): 0.0;
+        res = calculateDirLight(dir_lights[light_index], surface, shadow, norm, view_dir);
+    } else if (light_type == 1){
+        PointLight light = fetchPointLight(light_index);
+        float shadow = receive_shadows? pointShadow(light_index, light, frag_pos, norm): 0.0;
+        res = calculatePointLight(light, shadow, surface, norm, view_dir, frag_pos);
+    } else if (light_type == 2){
+        float shadow = receive_shadows? spotShadow(spot_lights[light_index], frag_pos, norm): 0.0;
+        res = calculateSpotLight(spot_lights[light_index], shadow, surface, norm, view_dir, frag_pos);
+    } else {
+        res = texture(g_emissive, uv).rgb + environmentLighting(surface, norm, view_dir);
+    }
+
+    color = vec4(res, 1.0);
+}
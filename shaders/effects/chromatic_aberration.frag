@@ -0,0 +1,18 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 color;
+
+uniform sampler2D image;
+uniform vec2 texel_size;
+// Offset of the red and blue channels at the screen corners, in pixels
+uniform float strength;
+
+void main(){
+    vec2 offset = (uv - 0.5) * 2.0 * strength * texel_size;
+    float r = texture(image, uv + offset).r;
+    float g = texture(image, uv).g;
+    float b = texture(image, uv - offset).b;
+    color = vec4(r, g, b, 1.0);
+}
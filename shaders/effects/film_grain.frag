@@ -0,0 +1,18 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 color;
+
+uniform sampler2D image;
+uniform float time;
+uniform float strength;
+
+float random(vec2 p){
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main(){
+    float noise = random(uv + fract(time)) - 0.5;
+    color = vec4(texture(image, uv).rgb + strength * noise, 1.0);
+}
@@ -0,0 +1,56 @@
+#version 330 core
+
+// Fast approximate anti-aliasing, after Timothy Lottes' FXAA. Pixels on a luma edge are blurred along the edge.
+
+in vec2 uv;
+
+out vec4 color;
+
+uniform sampler2D image;
+uniform vec2 texel_size;
+
+// Local contrast below which a pixel isn't on an edge, absolute and relative to the brightest neighbour
+const float EDGE_THRESHOLD_MIN = 1.0 / 32.0;
+const float EDGE_THRESHOLD = 1.0 / 8.0;
+// Longest blur along an edge, in pixels
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+
+// The image is linear, so approximate perceptual luma with a square root
+float luma(vec3 rgb){
+    return sqrt(dot(rgb, vec3(0.299, 0.587, 0.114)));
+}
+
+vec3 sampleAt(vec2 offset){
+    return texture(image, uv + offset).rgb;
+}
+
+void main(){
+    vec3 rgb_m = sampleAt(vec2(0.0));
+    float luma_m = luma(rgb_m);
+    float luma_nw = luma(sampleAt(vec2(-1.0, 1.0) * texel_size));
+    float luma_ne = luma(sampleAt(vec2(1.0, 1.0) * texel_size));
+    float luma_sw = luma(sampleAt(vec2(-1.0, -1.0) * texel_size));
+    float luma_se = luma(sampleAt(vec2(1.0, -1.0) * texel_size));
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    if (luma_max - luma_min < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD)) {
+        color = vec4(rgb_m, 1.0);
+        return;
+    }
+
+    // Perpendicular to the luma gradient, i.e. along the edge
+    vec2 dir = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, -SPAN_MAX, SPAN_MAX) * texel_size;
+
+    vec3 near = 0.5 * (sampleAt(dir * (1.0 / 3.0 - 0.5)) + sampleAt(dir * (2.0 / 3.0 - 0.5)));
+    vec3 far = 0.5 * near + 0.25 * (sampleAt(dir * -0.5) + sampleAt(dir * 0.5));
+
+    // The wider blur crossed into another edge if it left the local luma range
+    float luma_far = luma(far);
+    color = vec4(luma_far < luma_min || luma_far > luma_max ? near : far, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 color;
+
+uniform sampler2D image;
+
+void main(){
+    float luminance = dot(texture(image, uv).rgb, vec3(0.2126, 0.7152, 0.0722));
+    color = vec4(vec3(luminance), 1.0);
+}
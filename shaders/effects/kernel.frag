@@ -0,0 +1,21 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 color;
+
+uniform sampler2D image;
+uniform vec2 texel_size;
+// Row major, top row first
+uniform float kernel[9];
+
+void main(){
+    vec3 col = vec3(0.0);
+    for(int y = 0; y<3; y++){
+        for(int x = 0; x<3; x++){
+            vec2 offset = vec2(x - 1, 1 - y) * texel_size;
+            col += kernel[3*y + x] * texture(image, uv + offset).rgb;
+        }
+    }
+    color = vec4(col, 1.0);
+}
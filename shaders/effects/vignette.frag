@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 color;
+
+uniform sampler2D image;
+// Distance from the centre where darkening starts, and how far it takes to reach black, in units of half
+// the screen diagonal
+uniform float radius;
+uniform float softness;
+
+void main(){
+    float d = length(uv - 0.5) / length(vec2(0.5));
+    float vignette = 1.0 - smoothstep(radius, radius + softness, d);
+    color = vec4(texture(image, uv).rgb * vignette, 1.0);
+}
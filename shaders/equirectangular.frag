@@ -0,0 +1,18 @@
+#version 330 core
+
+// Projects an equirectangular panorama onto a cube map face.
+
+in vec3 direction;
+
+out vec4 color;
+
+uniform sampler2D panorama;
+
+const float PI = 3.14159265359;
+
+void main(){
+    vec3 d = normalize(direction);
+    // The panorama's first row, the top of the sky, is at t = 0
+    vec2 uv = vec2(atan(d.z, d.x) / (2.0 * PI) + 0.5, 0.5 - asin(d.y) / PI);
+    color = vec4(texture(panorama, uv).rgb, 1.0);
+}
@@ -0,0 +1,74 @@
+#version 330 core
+
+layout (location = 0) out vec3 g_position;
+layout (location = 1) out vec3 g_normal;
+// base colour and ambient occlusion
+layout (location = 2) out vec4 g_albedo_ao;
+// metallic, roughness and 1 where shadows fall on the surface
+layout (location = 3) out vec3 g_metallic_roughness;
+layout (location = 4) out vec3 g_emissive;
+
+in vec3 frag_pos;
+in vec3 normal;
+in vec2 uv;
+
+// Metallic-roughness material, see src/material.rs. Each factor is multiplied with its map when the map is present.
+struct Material{
+    vec3 base_colour;
+    float metallic;
+    float roughness;
+    vec3 emissive;
+
+    sampler2D base_colour_map;
+    sampler2D metallic_map;
+    sampler2D roughness_map;
+    sampler2D ao_map;
+    sampler2D emissive_map;
+
+    bool has_base_colour_map;
+    bool has_metallic_map;
+    bool has_roughness_map;
+    bool has_ao_map;
+    bool has_emissive_map;
+};
+
+struct Surface{
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    float ao;
+};
+
+uniform Material material;
+uniform bool receive_shadows;
+
+Surface sampleSurface(){
+    Surface surface;
+    surface.albedo = material.base_colour;
+    surface.metallic = material.metallic;
+    surface.roughness = material.roughness;
+    surface.ao = 1.0;
+
+    if (material.has_base_colour_map) surface.albedo *= texture(material.base_colour_map, uv).rgb;
+    if (material.has_metallic_map) surface.metallic *= texture(material.metallic_map, uv).r;
+    if (material.has_roughness_map) surface.roughness *= texture(material.roughness_map, uv).r;
+    if (material.has_ao_map) surface.ao = texture(material.ao_map, uv).r;
+
+    return surface;
+}
+
+vec3 emission(){
+    vec3 emissive = material.emissive;
+    if (material.has_emissive_map) emissive *= texture(material.emissive_map, uv).rgb;
+    return emissive;
+}
+
+void main() {
+    Surface surface = sampleSurface();
+
+    g_position = frag_pos;
+    g_normal = normalize(normal);
+    g_albedo_ao = vec4(surface.albedo, surface.ao);
+    g_metallic_roughness = vec3(surface.metallic, surface.roughness, float(receive_shadows));
+    g_emissive = emission();
+}
@@ -0,0 +1,28 @@
+#version 330 core
+
+// Fullscreen quad for rendering into cube map faces and lookup tables, drawn as a 4 vertex triangle strip
+// without any vertex buffers.
+
+out vec2 uv;
+// Cube map direction of the pixel, unnormalised
+out vec3 direction;
+
+// Cube map face being drawn, in +x, -x, +y, -y, +z, -z order
+uniform int face;
+
+// Maps (s, t, 1) in [-1, 1] to a direction, following the face orientation rules of the OpenGL spec
+const mat3 faces[6] = mat3[6](
+    mat3(vec3(0.0, 0.0, -1.0), vec3(0.0, -1.0, 0.0), vec3(1.0, 0.0, 0.0)),
+    mat3(vec3(0.0, 0.0, 1.0), vec3(0.0, -1.0, 0.0), vec3(-1.0, 0.0, 0.0)),
+    mat3(vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 0.0)),
+    mat3(vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), vec3(0.0, -1.0, 0.0)),
+    mat3(vec3(1.0, 0.0, 0.0), vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, 1.0)),
+    mat3(vec3(-1.0, 0.0, 0.0), vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, -1.0))
+);
+
+void main(){
+    vec2 pos = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1)) * 2.0 - 1.0;
+    uv = 0.5 * pos + 0.5;
+    direction = faces[face] * vec3(pos, 1.0);
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
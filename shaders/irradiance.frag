@@ -0,0 +1,34 @@
+#version 330 core
+
+// Cosine weighted convolution of the environment over the hemisphere around each normal.
+
+in vec3 direction;
+
+out vec4 color;
+
+uniform samplerCube environment;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+void main(){
+    vec3 normal = normalize(direction);
+    vec3 up = abs(normal.y) < 0.999? vec3(0.0, 1.0, 0.0): vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for(float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA){
+        for(float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA){
+            vec3 tangent_sample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 sample_dir = tangent_sample.x * right + tangent_sample.y * up + tangent_sample.z * normal;
+
+            // A low mip level is plenty for such a wide filter and avoids aliasing
+            irradiance += textureLod(environment, sample_dir, 4.0).rgb * cos(theta) * sin(theta);
+            samples++;
+        }
+    }
+
+    color = vec4(PI * irradiance / samples, 1.0);
+}
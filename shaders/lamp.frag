@@ -2,11 +2,14 @@
 
 out vec4 color;
 
+// Values above 1 make the lamps bright enough to bloom
+uniform float brightness;
+
 void main() {
     /* if (gl_FragCoord.x > 400){
         color = vec4(1.0, 0.0, 0.0, 1.0);
     } else {
         color = vec4(0.0, 1.0, 0.0, 1.0);
     } */
-    color = vec4(1.0);
-}
\ No newline at end of file
+    color = vec4(vec3(brightness), 1.0);
+}
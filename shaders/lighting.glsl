@@ -0,0 +1,448 @@
+// Lighting shared by the forward and deferred pipelines: light data, shadow lookups and the PBR BRDF. Included by
+// shader.frag and deferred.frag, see Shader::new in src/shader.rs.
+
+// Cascaded shadow map of the first directional light, see src/shadow.rs
+uniform sampler2DArray shadow_map;
+uniform int cascade_count;
+uniform mat4 cascade_transforms[4];
+// View space distance where each cascade ends
+uniform float cascade_splits[4];
+uniform mat4 camera_view;
+// The cascades again, read with hardware depth comparison
+uniform sampler2DArrayShadow shadow_map_compare;
+// 0 is hardware PCF, 1 is rotated Poisson disk PCF and 2 is PCSS, for every kind of light. See ShadowFilter in
+// src/scene.rs.
+uniform int shadow_filter;
+// Poisson disk radius in texels
+uniform float shadow_filter_radius;
+// Tangent of the angle a light's disc covers, which sets how fast PCSS penumbrae widen
+uniform float shadow_light_size;
+
+// Point light shadows, see PointShadowMaps in src/shadow.rs. The cube faces of shadowed light i are layers
+// 6i to 6i + 5, in +x, -x, +y, -y, +z, -z order.
+uniform sampler2DArray point_shadow_maps;
+uniform int point_shadow_count;
+uniform mat4 point_shadow_faces[6];
+uniform float point_shadow_far;
+
+// Spot light shadows, see SpotShadowAtlas in src/shadow.rs. Each shadowed light's transform and tile are part of
+// its light data.
+uniform sampler2D spot_shadow_atlas;
+uniform float spot_shadow_near;
+uniform float spot_shadow_far;
+
+// Must match the constants in src/light.rs
+#define MAX_DIRECTIONAL_LIGHTS 4
+#define MAX_SPOT_LIGHTS 16
+
+struct PointLight{
+    vec3 pos;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    // constant, linear and quadratic terms
+    vec3 attenuation;
+
+    // constant, slope and normal offset terms of ShadowBias in src/light.rs
+    vec3 shadow_bias;
+};
+
+struct DirLight {
+    vec3 direction;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    // constant, slope and normal offset terms of ShadowBias in src/light.rs
+    vec3 shadow_bias;
+};
+
+struct SpotLight {
+    vec3 pos;
+    vec3 direction;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    // cosines of the inner and outer cutoff angles
+    vec2 cutoff;
+
+    // world space to shadow clip space, and the offset and scale of the light's atlas tile with w 1 when shadowed
+    mat4 shadow_transform;
+    vec4 shadow_rect;
+    vec3 shadow_bias;
+};
+
+layout (std140) uniform Lights {
+    // number of directional, point and spot lights in use
+    ivec4 light_counts;
+    DirLight dir_lights[MAX_DIRECTIONAL_LIGHTS];
+    SpotLight spot_lights[MAX_SPOT_LIGHTS];
+};
+
+uniform samplerBuffer point_lights; // position, ambient, diffuse, specular, attenuation and shadow bias texels per light
+
+// Image based lighting from the scene's environment map, see src/ibl.rs
+uniform bool has_environment;
+uniform samplerCube irradiance_map;
+uniform samplerCube prefiltered_map;
+uniform sampler2D brdf_lut;
+uniform float prefiltered_levels;
+
+struct Surface{
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    float ao;
+};
+
+const float PI = 3.14159265359;
+
+PointLight fetchPointLight(int i){
+    PointLight light;
+    light.pos = texelFetch(point_lights, 6*i).xyz;
+    light.ambient = texelFetch(point_lights, 6*i + 1).xyz;
+    light.diffuse = texelFetch(point_lights, 6*i + 2).xyz;
+    light.specular = texelFetch(point_lights, 6*i + 3).xyz;
+    light.attenuation = texelFetch(point_lights, 6*i + 4).xyz;
+    light.shadow_bias = texelFetch(point_lights, 6*i + 5).xyz;
+    return light;
+}
+
+// Fraction of each cascade's depth range, at its far end, that fades into the next cascade
+const float CASCADE_BLEND = 0.1;
+// Widest PCSS blocker search and penumbra in texels, which keeps shadows far from their casters affordable
+const float MAX_PENUMBRA = 16.0;
+
+const vec2 POISSON_DISK[16] = vec2[](
+    vec2(-0.94201624, -0.39906216), vec2(0.94558609, -0.76890725), vec2(-0.09418410, -0.92938870), vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432), vec2(-0.81544232, -0.87912464), vec2(-0.38277543, 0.27676845), vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554), vec2(0.53742981, -0.47373420), vec2(-0.26496911, -0.41893023), vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507), vec2(-0.81409955, 0.91437590), vec2(0.19984126, 0.78641367), vec2(0.14383161, -0.14100790)
+);
+
+// Random rotation of the Poisson disk for this pixel
+mat2 poissonRotation(){
+    float angle = 2.0 * PI * fract(sin(dot(gl_FragCoord.xy, vec2(12.9898, 78.233))) * 43758.5453);
+    float s = sin(angle), c = cos(angle);
+    return mat2(c, s, -s, c);
+}
+
+// Depth bias in world units for a shadow map texel `texel` world units wide. bias holds the constant, slope and
+// normal offset terms of ShadowBias in src/light.rs; the slope term grows with the tangent of the angle between
+// the normal and the light, capped so grazing surfaces don't lose their shadows altogether.
+float depthBias(vec3 bias, float texel, vec3 norm, vec3 light_dir){
+    float n_dot_l = clamp(dot(norm, light_dir), 0.0, 1.0);
+    float tan_theta = min(sqrt(1.0 - n_dot_l * n_dot_l) / max(n_dot_l, 1e-3), 10.0);
+    return bias.x + bias.y * texel * tan_theta;
+}
+
+float cascadeShadow(vec3 frag_pos, vec3 norm, vec3 light_dir, vec3 bias, int cascade){
+    mat4 transform = cascade_transforms[cascade];
+    float size = float(textureSize(shadow_map, 0).x);
+    // Cascades are orthographic, so texel size and depth per world unit come straight from the transform
+    float texel = 2.0 / (length(vec3(transform[0][0], transform[1][0], transform[2][0])) * size);
+    float depth_scale = 0.5 * length(vec3(transform[0][2], transform[1][2], transform[2][2]));
+
+    vec4 lightspace_pos = transform * vec4(frag_pos + norm * bias.z * texel, 1.0);
+    vec3 lightspace_coords = 0.5 * lightspace_pos.xyz / lightspace_pos.w + 0.5;
+    if (lightspace_coords.z > 1.0){
+        return 0.0;
+    }
+    float current_depth = lightspace_coords.z - depthBias(bias, texel, norm, light_dir) * depth_scale;
+
+    if (shadow_filter == 0){
+        // Each lookup compares and blends a 2x2 block, so four of them cover a 3x3 area
+        float lit = 0.0;
+        for(int i = 0; i<4; i++){
+            vec2 offset = (vec2(i & 1, i >> 1) - 0.5) / size;
+            lit += texture(shadow_map_compare, vec4(lightspace_coords.xy + offset, cascade, current_depth));
+        }
+        return 1.0 - lit/4.0;
+    }
+
+    mat2 rotation = poissonRotation();
+    float radius = shadow_filter_radius;
+    if (shadow_filter == 2){
+        // Blocker search over the region of the map the light's disc could see occluders in
+        float search = clamp(shadow_light_size * current_depth / depth_scale / texel, 1.0, MAX_PENUMBRA);
+        float blockers = 0.0;
+        float blocker_depth = 0.0;
+        for(int i = 0; i<16; i++){
+            float depth = texture(shadow_map, vec3(lightspace_coords.xy + rotation * POISSON_DISK[i] * search / size, cascade)).r;
+            if (depth < current_depth){
+                blockers += 1.0;
+                blocker_depth += depth;
+            }
+        }
+        if (blockers == 0.0){
+            return 0.0;
+        }
+
+        // Penumbrae widen with the distance from the receiver back to its blockers
+        float blocker_distance = (current_depth - blocker_depth / blockers) / depth_scale;
+        radius = clamp(shadow_light_size * blocker_distance / texel, 1.0, MAX_PENUMBRA);
+    }
+
+    float lit = 0.0;
+    for(int i = 0; i<16; i++){
+        lit += texture(shadow_map_compare, vec4(lightspace_coords.xy + rotation * POISSON_DISK[i] * radius / size, cascade, current_depth));
+    }
+    return 1.0 - lit/16.0;
+}
+
+float calculateShadow(DirLight light, vec3 frag_pos, vec3 norm){
+    float depth = -(camera_view * vec4(frag_pos, 1.0)).z;
+    vec3 light_dir = normalize(-light.direction);
+
+    for(int i = 0; i<cascade_count; i++){
+        if (depth < cascade_splits[i]){
+            float shadow = cascadeShadow(frag_pos, norm, light_dir, light.shadow_bias, i);
+
+            // Blend towards the next cascade so the change in resolution doesn't show as a seam
+            float start = i == 0? 0.0: cascade_splits[i - 1];
+            float blend_start = cascade_splits[i] - CASCADE_BLEND * (cascade_splits[i] - start);
+            if (i + 1 < cascade_count && depth > blend_start){
+                shadow = mix(shadow, cascadeShadow(frag_pos, norm, light_dir, light.shadow_bias, i + 1), (depth - blend_start) / (cascade_splits[i] - blend_start));
+            }
+            return shadow;
+        }
+    }
+
+    // Beyond the last cascade
+    return 0.0;
+}
+
+// Offsets towards the edges and corners of a cube, for filtering point shadows
+const vec3 POINT_SHADOW_OFFSETS[20] = vec3[](
+    vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+    vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+    vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+    vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+    vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+int cubeFace(vec3 direction){
+    vec3 a = abs(direction);
+    if (a.x >= a.y && a.x >= a.z) return direction.x > 0.0? 0: 1;
+    if (a.y >= a.z) return direction.y > 0.0? 2: 3;
+    return direction.z > 0.0? 4: 5;
+}
+
+// Distance from a point light to the closest occluder in `direction`
+float pointShadowDistance(int light, vec3 direction){
+    int face = cubeFace(direction);
+    vec4 face_pos = point_shadow_faces[face] * vec4(direction, 1.0);
+    vec2 face_coords = 0.5 * face_pos.xy / face_pos.w + 0.5;
+    return texture(point_shadow_maps, vec3(face_coords, 6 * light + face)).r * point_shadow_far;
+}
+
+float pointShadow(int light, PointLight point_light, vec3 frag_pos, vec3 norm){
+    // Cube faces cover 90 degrees, so a texel at distance d is 2d / size wide
+    float texel = 2.0 * length(frag_pos - point_light.pos) / float(textureSize(point_shadow_maps, 0).x);
+    vec3 to_frag = frag_pos + norm * point_light.shadow_bias.z * texel - point_light.pos;
+    float current_distance = length(to_frag);
+    if (light >= point_shadow_count || current_distance > point_shadow_far){
+        return 0.0;
+    }
+    current_distance -= depthBias(point_light.shadow_bias, texel, norm, normalize(-to_frag));
+
+    if (shadow_filter == 0){
+        // Filter over a region that grows with distance, so the penumbra covers a similar angle everywhere
+        float radius = 0.02 * current_distance;
+        float shadow = 0.0;
+        for(int i = 0; i<20; i++){
+            shadow += current_distance > pointShadowDistance(light, to_frag + POINT_SHADOW_OFFSETS[i] * radius)? 1.0: 0.0;
+        }
+        return shadow/20.0;
+    }
+
+    // The Poisson disk lies across the direction to the fragment, measured in texels at the fragment's distance
+    vec3 axis = to_frag / length(to_frag);
+    vec3 tangent = normalize(cross(abs(axis.y) < 0.99? vec3(0.0, 1.0, 0.0): vec3(1.0, 0.0, 0.0), axis));
+    mat2x3 disk = mat2x3(tangent, cross(axis, tangent)) * texel;
+    mat2 rotation = poissonRotation();
+    float radius = shadow_filter_radius;
+    if (shadow_filter == 2){
+        float search = clamp(shadow_light_size * current_distance / texel, 1.0, MAX_PENUMBRA);
+        float blockers = 0.0;
+        float blocker_distance = 0.0;
+        for(int i = 0; i<16; i++){
+            float occluder = pointShadowDistance(light, to_frag + disk * (rotation * POISSON_DISK[i] * search));
+            if (occluder < current_distance){
+                blockers += 1.0;
+                blocker_distance += occluder;
+            }
+        }
+        if (blockers == 0.0){
+            return 0.0;
+        }
+        radius = clamp(shadow_light_size * (current_distance - blocker_distance / blockers) / texel, 1.0, MAX_PENUMBRA);
+    }
+
+    float shadow = 0.0;
+    for(int i = 0; i<16; i++){
+        shadow += current_distance > pointShadowDistance(light, to_frag + disk * (rotation * POISSON_DISK[i] * radius))? 1.0: 0.0;
+    }
+    return shadow/16.0;
+}
+
+// Distance along a spot light's axis of a depth from its perspective shadow map
+float linearSpotDepth(float depth){
+    float z = 2.0 * depth - 1.0;
+    return 2.0 * spot_shadow_near * spot_shadow_far / (spot_shadow_far + spot_shadow_near - z * (spot_shadow_far - spot_shadow_near));
+}
+
+float spotShadow(SpotLight light, vec3 frag_pos, vec3 norm){
+    if (light.shadow_rect.w == 0.0){
+        return 0.0;
+    }
+
+    // The projection covers the outer cone, so a texel at axis distance d is 2d tan(outer) / tile size wide
+    vec2 atlas_size = vec2(textureSize(spot_shadow_atlas, 0));
+    float tan_outer = sqrt(1.0 - light.cutoff.y * light.cutoff.y) / light.cutoff.y;
+    float texel = 2.0 * (light.shadow_transform * vec4(frag_pos, 1.0)).w * tan_outer / (light.shadow_rect.z * atlas_size.x);
+
+    vec4 lightspace_pos = light.shadow_transform * vec4(frag_pos + norm * light.shadow_bias.z * texel, 1.0);
+    vec3 coords = 0.5 * lightspace_pos.xyz / lightspace_pos.w + 0.5;
+    if (lightspace_pos.w <= 0.0 || coords.z > 1.0
+        || any(lessThan(coords.xy, vec2(0.0))) || any(greaterThan(coords.xy, vec2(1.0)))){
+        return 0.0;
+    }
+
+    // Filtering is clamped to the light's tile, so it never reads another light's depths
+    vec2 atlas_texel = 1.0/atlas_size;
+    vec2 tile_min = light.shadow_rect.xy + 0.5 * atlas_texel;
+    vec2 tile_max = light.shadow_rect.xy + light.shadow_rect.z - 0.5 * atlas_texel;
+    vec2 atlas_coords = light.shadow_rect.xy + coords.xy * light.shadow_rect.z;
+
+    // Depths are compared linearly, so the bias means the same distance near the light and far from it
+    float current_depth = lightspace_pos.w - depthBias(light.shadow_bias, texel, norm, normalize(light.pos - frag_pos));
+    if (shadow_filter == 0){
+        float shadow = 0.0;
+        for(int x = -1; x<=1; x++){
+            for(int y = -1; y<=1; y++){
+                float closest_depth = linearSpotDepth(texture(spot_shadow_atlas, clamp(atlas_coords + vec2(x, y) * atlas_texel, tile_min, tile_max)).r);
+                shadow += current_depth > closest_depth? 1.0: 0.0;
+            }
+        }
+        return shadow/9.0;
+    }
+
+    mat2 rotation = poissonRotation();
+    float radius = shadow_filter_radius;
+    if (shadow_filter == 2){
+        float search = clamp(shadow_light_size * current_depth / texel, 1.0, MAX_PENUMBRA);
+        float blockers = 0.0;
+        float blocker_depth = 0.0;
+        for(int i = 0; i<16; i++){
+            float depth = linearSpotDepth(texture(spot_shadow_atlas, clamp(atlas_coords + rotation * POISSON_DISK[i] * search * atlas_texel, tile_min, tile_max)).r);
+            if (depth < current_depth){
+                blockers += 1.0;
+                blocker_depth += depth;
+            }
+        }
+        if (blockers == 0.0){
+            return 0.0;
+        }
+        radius = clamp(shadow_light_size * (current_depth - blocker_depth / blockers) / texel, 1.0, MAX_PENUMBRA);
+    }
+
+    float shadow = 0.0;
+    for(int i = 0; i<16; i++){
+        float closest_depth = linearSpotDepth(texture(spot_shadow_atlas, clamp(atlas_coords + rotation * POISSON_DISK[i] * radius * atlas_texel, tile_min, tile_max)).r);
+        shadow += current_depth > closest_depth? 1.0: 0.0;
+    }
+    return shadow/16.0;
+}
+
+// Trowbridge-Reitz GGX normal distribution
+float distributionGGX(float n_dot_h, float roughness){
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Smith's method with Schlick-GGX for both the light and view directions
+float geometrySmith(float n_dot_v, float n_dot_l, float roughness){
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+vec3 fresnelSchlick(float cos_theta, vec3 f0){
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Cook-Torrance BRDF times the cosine term, with the light's diffuse colour lighting the diffuse lobe and its
+// specular colour the specular lobe. The ambient colour stands in for indirect light.
+vec3 shade(Surface surface, vec3 light_dir, vec3 ambient, vec3 diffuse, vec3 specular, vec3 norm, vec3 view_dir){
+    vec3 halfway = normalize(view_dir + light_dir);
+    float n_dot_l = max(dot(norm, light_dir), 0.0);
+    float n_dot_v = max(dot(norm, view_dir), 1e-4);
+    float n_dot_h = max(dot(norm, halfway), 0.0);
+    float roughness = clamp(surface.roughness, 0.04, 1.0);
+
+    vec3 f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+    vec3 f = fresnelSchlick(max(dot(halfway, view_dir), 0.0), f0);
+    float d = distributionGGX(n_dot_h, roughness);
+    float g = geometrySmith(n_dot_v, n_dot_l, roughness);
+
+    vec3 k_d = (1.0 - f) * (1.0 - surface.metallic);
+    vec3 brdf_specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 1e-4);
+
+    // Light colours are scaled by PI, as in three.js, so they keep the brightness they had under Blinn-Phong
+    return surface.albedo * surface.ao * ambient
+        + (k_d * surface.albedo * diffuse + PI * brdf_specular * specular) * n_dot_l;
+}
+
+// Fresnel-Schlick averaged over the lobe, so rough surfaces don't get bright rims from the environment
+vec3 fresnelSchlickRoughness(float cos_theta, vec3 f0, float roughness){
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Diffuse irradiance plus split sum specular from the prefiltered environment
+vec3 environmentLighting(Surface surface, vec3 norm, vec3 view_dir){
+    if (!has_environment){
+        return vec3(0.0);
+    }
+
+    float n_dot_v = max(dot(norm, view_dir), 0.0);
+    vec3 f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+    vec3 f = fresnelSchlickRoughness(n_dot_v, f0, surface.roughness);
+    vec3 k_d = (1.0 - f) * (1.0 - surface.metallic);
+
+    vec3 diffuse = texture(irradiance_map, norm).rgb * surface.albedo;
+    vec3 prefiltered = textureLod(prefiltered_map, reflect(-view_dir, norm), surface.roughness * (prefiltered_levels - 1.0)).rgb;
+    vec2 brdf = texture(brdf_lut, vec2(n_dot_v, surface.roughness)).rg;
+    vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+
+    return (k_d * diffuse + specular) * surface.ao;
+}
+
+vec3 calculateDirLight(DirLight dir_light, Surface surface, float shadow, vec3 norm, vec3 view_dir){
+    return (1.0 - shadow) * shade(surface, normalize(-dir_light.direction), dir_light.ambient, dir_light.diffuse, dir_light.specular, norm, view_dir);
+}
+
+vec3 calculatePointLight(PointLight point_light, float shadow, Surface surface, vec3 norm, vec3 view_dir, vec3 frag_pos){
+    vec3 light_dir = normalize(point_light.pos - frag_pos);
+    float d = length(point_light.pos - frag_pos);
+    float attenuation = (1.0 - shadow)/(point_light.attenuation.x + point_light.attenuation.y * d + point_light.attenuation.z * d * d);
+
+    return attenuation * shade(surface, light_dir, point_light.ambient, point_light.diffuse, point_light.specular, norm, view_dir);
+}
+
+vec3 calculateSpotLight(SpotLight spot_light, float shadow, Surface surface, vec3 norm, vec3 view_dir, vec3 frag_pos){
+    vec3 light_dir = normalize(spot_light.pos - frag_pos);
+
+    float theta = dot(normalize(-spot_light.direction),light_dir);
+    float attenuation = (1.0 - shadow) * clamp((theta - spot_light.cutoff.y)/(spot_light.cutoff.x - spot_light.cutoff.y), 0.0, 1.0);
+
+    return attenuation * shade(surface, light_dir, spot_light.ambient, spot_light.diffuse, spot_light.specular, norm, view_dir);
+}
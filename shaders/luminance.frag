@@ -0,0 +1,15 @@
+#version 330 core
+
+// Log luminance of the HDR scene. Averaging logs over the mip chain gives the geometric mean, which a few
+// very bright pixels can't dominate.
+
+in vec2 uv;
+
+out float log_luminance;
+
+uniform sampler2D scene;
+
+void main(){
+    float luminance = dot(texture(scene, uv).rgb, vec3(0.2126, 0.7152, 0.0722));
+    log_luminance = log(max(luminance, 1e-4));
+}
@@ -0,0 +1,11 @@
+#version 330 core
+
+in vec3 world_pos;
+
+uniform vec3 light_pos;
+uniform float far;
+
+// Linear distance to the light rather than perspective depth, so it can be compared from any face
+void main(){
+    gl_FragDepth = length(world_pos - light_pos) / far;
+}
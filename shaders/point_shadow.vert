@@ -0,0 +1,10 @@
+#version 330 core
+
+layout(location = 0) in vec3 a_pos;
+
+uniform mat4 u_model;
+
+// World space, projected onto each cube face by the geometry shader
+void main(){
+    gl_Position = u_model * vec4(a_pos, 1.0);
+}
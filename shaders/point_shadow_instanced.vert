@@ -0,0 +1,9 @@
+#version 330 core
+
+layout(location = 0) in vec3 a_pos;
+layout(location = 2) in mat4 a_model;
+
+// World space, projected onto each cube face by the geometry shader
+void main(){
+    gl_Position = a_model * vec4(a_pos, 1.0);
+}
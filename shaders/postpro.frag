@@ -6,37 +6,57 @@ out vec4 color;
 
 uniform sampler2D texture1;
 
-void main(){
-    color = vec4(texture(texture1, uv).rgb, 1.0);
-    //color = vec4(vec3(1.0 - texture(texture1, uv)), 1.0);
-    /* float offset = 1.0/300.0;
-
-    vec2 offsets[9] = vec2[](
-        vec2(-offset, offset), // top-left
-        vec2( 0.0f, offset), // top-center
-        vec2( offset, offset), // top-right
-        vec2(-offset, 0.0f), // center-left
-        vec2( 0.0f, 0.0f), // center-center
-        vec2( offset, 0.0f), // center-right
-        vec2(-offset, -offset), // bottom-left
-        vec2( 0.0f, -offset), // bottom-center
-        vec2( offset, -offset) // bottom-right
-    );
-    float kernel[9] = float[](
-        1, 1, 1,
-        1, -8, 1,
-        1, 1, 1
-    );
-
-    vec3 sample[9];
-    for(int i = 0; i<9; i++){
-        sample[i] = kernel[i] * texture(texture1, uv + offsets[i]).rgb;
+// 0 clamps, 1 is Reinhard, 2 is ACES filmic and 3 is Uncharted 2, see ToneMapping in src/scene.rs
+uniform int tone_mapping;
+uniform float exposure;
+// Scale exposure so the adapted average luminance maps to middle grey
+uniform bool auto_exposure;
+uniform sampler2D adapted_luminance;
+
+const float MIDDLE_GREY = 0.18;
+
+// Blurred bright pixels from src/bloom.rs, added before tone mapping
+uniform bool bloom;
+uniform sampler2D bloom_texture;
+uniform float bloom_intensity;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x){
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// John Hable's filmic curve from Uncharted 2
+vec3 hable(vec3 x){
+    const float A = 0.15, B = 0.50, C = 0.10, D = 0.20, E = 0.02, F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 uncharted2(vec3 x){
+    const float WHITE_POINT = 11.2;
+    return hable(2.0 * x) / hable(vec3(WHITE_POINT));
+}
+
+vec3 toneMap(vec3 col){
+    float scale = exposure;
+    if (auto_exposure){
+        scale *= MIDDLE_GREY / max(texture(adapted_luminance, vec2(0.5)).r, 1e-4);
     }
-
-    vec3 col = vec3(0.0);
-    for(int i = 0; i<9; i++){
-        col += sample[i];
+    col *= scale;
+
+    if (tone_mapping == 1){
+        return col / (1.0 + col);
+    } else if (tone_mapping == 2){
+        return aces(col);
+    } else if (tone_mapping == 3){
+        return uncharted2(col);
     }
+    return clamp(col, 0.0, 1.0);
+}
 
-    color = vec4(col, 1.0); */
-}
\ No newline at end of file
+void main(){
+    vec3 hdr = texture(texture1, uv).rgb;
+    if (bloom){
+        hdr += bloom_intensity * texture(bloom_texture, uv).rgb;
+    }
+    color = vec4(toneMap(hdr), 1.0);
+}
@@ -0,0 +1,76 @@
+#version 330 core
+
+// Convolves the environment with the GGX lobe for one roughness, assuming the view direction equals the
+// normal and the reflection direction, as in Karis' split sum approximation.
+
+in vec3 direction;
+
+out vec4 color;
+
+uniform samplerCube environment;
+uniform float roughness;
+// Face size of the environment's base level
+uniform float source_size;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+float radicalInverse(uint bits){
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint i, uint n){
+    return vec2(float(i) / float(n), radicalInverse(i));
+}
+
+vec3 importanceSampleGGX(vec2 xi, vec3 n, float roughness){
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(n.z) < 0.999? vec3(0.0, 0.0, 1.0): vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+
+float distributionGGX(float n_dot_h, float roughness){
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+void main(){
+    vec3 n = normalize(direction);
+    vec3 v = n;
+
+    vec3 prefiltered = vec3(0.0);
+    float total_weight = 0.0;
+    for(uint i = 0u; i < SAMPLE_COUNT; i++){
+        vec3 h = importanceSampleGGX(hammersley(i, SAMPLE_COUNT), n, roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float n_dot_l = max(dot(n, l), 0.0);
+
+        if (n_dot_l > 0.0){
+            // Sample a mip level whose texels cover about as much solid angle as the sample does
+            float n_dot_h = max(dot(n, h), 0.0);
+            float pdf = distributionGGX(n_dot_h, roughness) * n_dot_h / (4.0 * max(dot(h, v), 1e-4)) + 1e-4;
+            float sample_angle = 1.0 / (float(SAMPLE_COUNT) * pdf);
+            float texel_angle = 4.0 * PI / (6.0 * source_size * source_size);
+            float level = roughness == 0.0? 0.0: 0.5 * log2(sample_angle / texel_angle);
+
+            prefiltered += textureLod(environment, l, level).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+
+    color = vec4(prefiltered / total_weight, 1.0);
+}
@@ -7,7 +7,10 @@ in vec2 uv;
 out vec4 color;
 
 uniform vec3 camera_pos;
-uniform samplerCube skybox;
+// Prefiltered environment from src/ibl.rs, so rough reflections blur instead of aliasing
+uniform samplerCube prefiltered_map;
+uniform float prefiltered_levels;
+uniform float roughness;
 
 void main(){
     vec3 i = normalize(frag_pos - camera_pos);
@@ -15,5 +18,5 @@ void main(){
     /* float ratio = 1.00 / 1.52;
     vec3 i = normalize(frag_pos - camera_pos);
     vec3 r = refract(i , normalize(normal), ratio); */
-    color = vec4(texture(skybox, r).rgb, 1.0);
-}
\ No newline at end of file
+    color = vec4(textureLod(prefiltered_map, r, roughness * (prefiltered_levels - 1.0)).rgb, 1.0);
+}
@@ -1,144 +1,117 @@
-#version 330 core
-
-in vec3 frag_pos;
-in vec3 normal;
-in vec2 uv;
-
-out vec4 color;
-
-uniform mat4 lightspace_transform;
-uniform sampler2D shadow_map;
-
-struct Material{
-    sampler2D texture_diffuse1;
-    sampler2D texture_specular1;
-    sampler2D emission;
-    float shininess;
-};
-
-struct PointLight{
-    vec3 pos;
-
-    vec3 ambient;
-    vec3 diffuse;
-    vec3 specular;
-
-    float c;
-    float l;
-    float q;
-};
-
-struct DirLight {
-    vec3 direction;
-
-    vec3 ambient;
-    vec3 diffuse;
-    vec3 specular;
-};
-
-struct SpotLight {
-    vec3 pos;
-    vec3 direction;
-
-    vec3 ambient;
-    vec3 diffuse;
-    vec3 specular;
-
-    float cutoff;
-    float outerCutoff;
-};
-
-uniform vec3 camera_pos;
-uniform DirLight dir_light;
-uniform SpotLight spot_light;
-uniform PointLight point_lights[6];
-uniform Material material;
-
-vec3 calculateDirLight(DirLight dir_light, vec3 norm, vec3 view_dir, vec3 frag_pos){
-    vec3 light_dir = normalize(-dir_light.direction);
-    vec4 lightspace_pos = lightspace_transform * vec4(frag_pos, 1.0);
-    vec3 lightspace_coords = lightspace_pos.xyz / lightspace_pos.w;
-    lightspace_coords = 0.5 * lightspace_coords + 0.5;
-    //float closest_depth = texture(shadow_map, lightspace_coords.xy).r;
-    float current_depth = lightspace_coords.z;
-    //float shadow = current_depth - max(0.05* (1.0-dot(norm, light_dir)), 0.005) > closest_depth? 1.0: 0.0;
-    float shadow = 0.0;
-    vec2 size = 1.0/textureSize(shadow_map, 0);
-
-    for(int x = -1; x<=1; x++){
-        for(int y = -1; y<=1; y++){
-            float closest_depth = texture(shadow_map, lightspace_coords.xy + vec2(x, y) * size).r;
-            shadow += current_depth - 0.005 > closest_depth? 1.0: 0.0;
-        }
-    }
-
-    shadow/=9.0;
-    
-    if (lightspace_coords.z > 1.0){
-        shadow = 0.0;
-    }
-
-    vec3 ambient = vec3(texture(material.texture_diffuse1, uv)) * dir_light.ambient;
-
-    float diff = max(dot(norm, light_dir), 0.0);
-    vec3 diffuse = diff*dir_light.diffuse*vec3(texture(material.texture_diffuse1, uv));
-
-    vec3 halfway = normalize(view_dir + light_dir);
-
-    float spec = pow(max(dot(norm, halfway), 0.0), material.shininess);
-    vec3 specular = spec * dir_light.specular * vec3(texture(material.texture_specular1, uv));
-
-    return (1.0 - shadow) * (ambient + diffuse + specular);
-    //return vec3(texture(shadow_map, lightspace_coords.xy).r);
-}
-
-vec3 calculatePointLight(PointLight point_light, vec3 norm, vec3 view_dir, vec3 frag_pos){
-    vec3 light_dir = normalize(point_light.pos - frag_pos);
-    float d = length(point_light.pos - frag_pos);
-    float attenuation = 1/(point_light.c + point_light.l * d + point_light.q * d * d);
-
-    vec3 ambient = vec3(texture(material.texture_diffuse1, uv)) * point_light.ambient;
-
-    float diff = max(dot(norm, light_dir), 0.0);
-    vec3 diffuse = diff*point_light.diffuse * vec3(texture(material.texture_diffuse1, uv));
-
-    vec3 halfway = normalize(view_dir + light_dir);
-
-    float spec = pow(max(dot(norm, halfway), 0.0), material.shininess);
-    vec3 specular = spec * point_light.specular * vec3(texture(material.texture_specular1, uv));
-
-    return attenuation * (ambient + diffuse + specular);
-}
-
-vec3 calculateSpotLight(SpotLight spot_light, vec3 norm, vec3 view_dir, vec3 frag_pos){
-    vec3 light_dir = normalize(spot_light.pos - frag_pos);
-
-    float theta = dot(normalize(-spot_light.direction),light_dir);
-    float attenuation = clamp((theta - spot_light.outerCutoff)/(spot_light.cutoff - spot_light.outerCutoff), 0.0, 1.0);
-
-    vec3 ambient = vec3(texture(material.texture_diffuse1, uv)) * spot_light.ambient;
-
-    float diff = max(dot(norm, light_dir), 0.0);
-    vec3 diffuse = diff*spot_light.diffuse * vec3(texture(material.texture_diffuse1, uv));
-
-    vec3 halfway = normalize(view_dir + light_dir);
-
-    float spec = pow(max(dot(norm, halfway), 0.0), material.shininess);
-    vec3 specular = spec * spot_light.specular * vec3(texture(material.texture_specular1, uv));
-
-    return attenuation * ( ambient + diffuse + specular );
-}
-
-void main() {
-    vec3 norm = normalize(normal);
-    vec3 view_dir = normalize(camera_pos - frag_pos);
-    vec3 res = vec3(0.0);
-    res += calculateDirLight(dir_light, norm, view_dir, frag_pos);
-
-    for(int i = 0; i<6; i++){
-       //res += calculatePointLight(point_lights[i], norm, view_dir, frag_pos);
-    }
-
-    //res+= calculateSpotLight(spot_light, norm, view_dir, frag_pos);
-    color = vec4(res, 1.0);
-}
\ No newline at end of file
+#version 330 core
+
+in vec3 frag_pos;
+in vec3 normal;
+in vec2 uv;
+in float view_depth;
+
+out vec4 color;
+
+#include "lighting.glsl"
+
+// Metallic-roughness material, see src/material.rs. Each factor is multiplied with its map when the map is present.
+struct Material{
+    vec3 base_colour;
+    float metallic;
+    float roughness;
+    vec3 emissive;
+
+    sampler2D base_colour_map;
+    sampler2D metallic_map;
+    sampler2D roughness_map;
+    sampler2D ao_map;
+    sampler2D emissive_map;
+
+    bool has_base_colour_map;
+    bool has_metallic_map;
+    bool has_roughness_map;
+    bool has_ao_map;
+    bool has_emissive_map;
+};
+
+// Point lights are culled per cluster, see src/cluster.rs
+uniform usamplerBuffer clusters; // offset into light_indices and light count per cluster
+uniform usamplerBuffer light_indices;
+uniform ivec3 cluster_grid;
+uniform vec2 screen_size;
+uniform float cluster_near;
+uniform float cluster_far;
+uniform bool cluster_heatmap;
+
+uniform vec3 camera_pos;
+uniform Material material;
+// Cleared for models that shadows shouldn't fall on
+uniform bool receive_shadows;
+
+// Screen space ambient occlusion, see src/ssao.rs
+uniform bool ssao;
+uniform sampler2D ssao_map;
+
+Surface sampleSurface(){
+    Surface surface;
+    surface.albedo = material.base_colour;
+    surface.metallic = material.metallic;
+    surface.roughness = material.roughness;
+    surface.ao = 1.0;
+
+    if (material.has_base_colour_map) surface.albedo *= texture(material.base_colour_map, uv).rgb;
+    if (material.has_metallic_map) surface.metallic *= texture(material.metallic_map, uv).r;
+    if (material.has_roughness_map) surface.roughness *= texture(material.roughness_map, uv).r;
+    if (material.has_ao_map) surface.ao = texture(material.ao_map, uv).r;
+
+    return surface;
+}
+
+vec3 emission(){
+    vec3 emissive = material.emissive;
+    if (material.has_emissive_map) emissive *= texture(material.emissive_map, uv).rgb;
+    return emissive;
+}
+
+int clusterIndex(){
+    ivec2 tile = ivec2(gl_FragCoord.xy / screen_size * vec2(cluster_grid.xy));
+    int slice = int(floor(log(view_depth / cluster_near) / log(cluster_far / cluster_near) * float(cluster_grid.z)));
+    tile = clamp(tile, ivec2(0), cluster_grid.xy - 1);
+    slice = clamp(slice, 0, cluster_grid.z - 1);
+    return tile.x + cluster_grid.x * (tile.y + cluster_grid.y * slice);
+}
+
+// Blue through green to red as the number of lights goes from 0 to 16
+vec3 heatmap(uint count){
+    float t = clamp(float(count) / 16.0, 0.0, 1.0);
+    return t < 0.5? mix(vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 0.0), 2.0*t): mix(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 2.0*t - 1.0);
+}
+
+void main() {
+    vec3 norm = normalize(normal);
+    vec3 view_dir = normalize(camera_pos - frag_pos);
+    Surface surface = sampleSurface();
+    if (ssao) surface.ao *= texture(ssao_map, gl_FragCoord.xy / screen_size).r;
+    vec3 res = emission() + environmentLighting(surface, norm, view_dir);
+
+    // Only the first directional light has a shadow map
+    for(int i = 0; i<light_counts.x; i++){
+        float shadow = receive_shadows && i == 0? calculateShadow(dir_lights[i], frag_pos, norm): 0.0;
+        res += calculateDirLight(dir_lights[i], surface, shadow, norm, view_dir);
+    }
+
+    uvec2 cluster = texelFetch(clusters, clusterIndex()).xy;
+    if (cluster_heatmap){
+        color = vec4(heatmap(cluster.y), 1.0);
+        return;
+    }
+
+    for(uint i = 0u; i<cluster.y; i++){
+        int light = int(texelFetch(light_indices, int(cluster.x + i)).r);
+        PointLight point_light = fetchPointLight(light);
+        float shadow = receive_shadows? pointShadow(light, point_light, frag_pos, norm): 0.0;
+        res += calculatePointLight(point_light, shadow, surface, norm, view_dir, frag_pos);
+    }
+
+    for(int i = 0; i<light_counts.z; i++){
+        float shadow = receive_shadows? spotShadow(spot_lights[i], frag_pos, norm): 0.0;
+        res += calculateSpotLight(spot_lights[i], shadow, surface, norm, view_dir, frag_pos);
+    }
+
+    color = vec4(res, 1.0);
+}
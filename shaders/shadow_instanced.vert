@@ -0,0 +1,11 @@
+#version 330 core
+
+layout(location = 0) in vec3 a_pos;
+layout(location = 2) in mat4 a_model;
+
+uniform mat4 lightspace_transform;
+
+// shadow.vert for instanced geometry, with each instance's model matrix as a vertex attribute
+void main(){
+    gl_Position = lightspace_transform * a_model * vec4(a_pos, 1.0);
+}
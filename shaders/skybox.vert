@@ -9,7 +9,13 @@ layout (std140) uniform Matrices{
     uniform mat4 u_view;
 };
 
+// A perspective projection even when the camera's isn't, since the sky only depends on direction
+uniform mat4 sky_projection;
+// Where the sky sits in the depth range, 1 or 0 with reversed-Z
+uniform float sky_depth;
+
 void main(){
     tex_coords = a_pos;
-    gl_Position = (u_proj * mat4(mat3(u_view)) * vec4(a_pos, 1.0)).xyww;
+    vec4 pos = sky_projection * mat4(mat3(u_view)) * vec4(a_pos, 1.0);
+    gl_Position = vec4(pos.xy, (2.0 * sky_depth - 1.0) * pos.w, pos.w);
 }
\ No newline at end of file
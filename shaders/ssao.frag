@@ -0,0 +1,61 @@
+#version 330 core
+
+// Screen space ambient occlusion. Samples a hemisphere around each pixel's normal in view space, randomly
+// rotated per pixel by the tiled noise texture, and counts the samples that end up behind nearer geometry.
+
+in vec2 uv;
+
+out float occlusion;
+
+layout (std140) uniform Matrices {
+    uniform mat4 u_projection;
+    uniform mat4 u_view;
+};
+
+uniform sampler2D g_position;
+uniform sampler2D g_normal;
+uniform sampler2D noise;
+// Screen size over noise texture size, so the noise tiles once per 4x4 pixels
+uniform vec2 noise_scale;
+
+uniform vec3 samples[64];
+uniform int sample_count;
+uniform float radius;
+uniform float bias;
+
+vec3 viewPosition(vec2 coords){
+    return (u_view * vec4(texture(g_position, coords).xyz, 1.0)).xyz;
+}
+
+void main(){
+    vec3 world_normal = texture(g_normal, uv).xyz;
+    // Nothing was drawn here, e.g. the skybox
+    if (dot(world_normal, world_normal) == 0.0) {
+        occlusion = 1.0;
+        return;
+    }
+
+    vec3 position = viewPosition(uv);
+    vec3 normal = normalize(mat3(u_view) * world_normal);
+    vec3 random = vec3(texture(noise, uv * noise_scale).xy, 0.0);
+
+    // Gram-Schmidt a tangent frame around the normal, rotated by the random vector
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    vec3 bitangent = cross(normal, tangent);
+    mat3 TBN = mat3(tangent, bitangent, normal);
+
+    float occluded = 0.0;
+    for (int i = 0; i < sample_count; i++) {
+        vec3 sample_pos = position + TBN * samples[i] * radius;
+
+        vec4 offset = u_projection * vec4(sample_pos, 1.0);
+        offset.xy = offset.xy / offset.w * 0.5 + 0.5;
+
+        float occluder_depth = viewPosition(offset.xy).z;
+        // Fade out occluders far outside the radius so edges against the background don't darken
+        float range_check = smoothstep(0.0, 1.0, radius / abs(position.z - occluder_depth));
+        occluded += (occluder_depth >= sample_pos.z + bias ? 1.0 : 0.0) * range_check;
+    }
+
+    occlusion = 1.0 - occluded / float(sample_count);
+}
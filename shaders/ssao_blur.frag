@@ -0,0 +1,20 @@
+#version 330 core
+
+// Box blur over one tile of the 4x4 noise texture, which removes the noise pattern from the occlusion.
+
+in vec2 uv;
+
+out float blurred;
+
+uniform sampler2D occlusion;
+
+void main(){
+    vec2 texel_size = 1.0 / vec2(textureSize(occlusion, 0));
+    float result = 0.0;
+    for (int x = -2; x < 2; x++) {
+        for (int y = -2; y < 2; y++) {
+            result += texture(occlusion, uv + vec2(x, y) * texel_size).r;
+        }
+    }
+    blurred = result / 16.0;
+}
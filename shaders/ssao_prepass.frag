@@ -0,0 +1,15 @@
+#version 330 core
+
+// World space position and normal for screen space ambient occlusion in the forward pipeline, laid out like
+// the first two G-buffer targets.
+
+layout (location = 0) out vec3 g_position;
+layout (location = 1) out vec3 g_normal;
+
+in vec3 frag_pos;
+in vec3 normal;
+
+void main() {
+    g_position = frag_pos;
+    g_normal = normalize(normal);
+}
@@ -0,0 +1,56 @@
+#version 330 core
+
+// Temporal anti-aliasing resolve, see src/taa.rs. Blends the jittered current frame into the history of the
+// previous frames, found by reprojecting each pixel's depth with last frame's camera.
+
+in vec2 uv;
+
+out vec4 color;
+
+uniform sampler2D current_frame;
+uniform sampler2D history;
+uniform sampler2D depth;
+
+// Of the jittered projection the current frame was drawn with
+uniform mat4 inverse_view_projection;
+uniform mat4 previous_view_projection;
+uniform bool has_history;
+// Weight of the current frame
+uniform float blend;
+
+float luminance(vec3 rgb){
+    return dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+}
+
+void main(){
+    vec3 current = texture(current_frame, uv).rgb;
+
+    vec4 world = inverse_view_projection * vec4(vec3(uv, texture(depth, uv).r) * 2.0 - 1.0, 1.0);
+    // Kept homogeneous, since the sky at infinite depth has no finite position under a reversed-Z projection
+    vec4 previous = previous_view_projection * world;
+    vec2 previous_uv = previous.xy / previous.w * 0.5 + 0.5;
+
+    // Nothing to blend with on the first frame or where the pixel was off screen
+    if (!has_history || any(lessThan(previous_uv, vec2(0.0))) || any(greaterThan(previous_uv, vec2(1.0)))) {
+        color = vec4(current, 1.0);
+        return;
+    }
+
+    // Clamp the history to the colours around the pixel this frame, so it can't ghost where the image changed
+    vec2 texel_size = 1.0 / vec2(textureSize(current_frame, 0));
+    vec3 low = current;
+    vec3 high = current;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec3 neighbour = texture(current_frame, uv + vec2(x, y) * texel_size).rgb;
+            low = min(low, neighbour);
+            high = max(high, neighbour);
+        }
+    }
+    vec3 previous_color = clamp(texture(history, previous_uv).rgb, low, high);
+
+    // Weighting by inverse luminance keeps a few very bright HDR samples from flickering
+    float current_weight = blend / (1.0 + luminance(current));
+    float history_weight = (1.0 - blend) / (1.0 + luminance(previous_color));
+    color = vec4((current * current_weight + previous_color * history_weight) / (current_weight + history_weight), 1.0);
+}
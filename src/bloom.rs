@@ -0,0 +1,99 @@
+use gl;
+
+use crate::framebuffer::Framebuffer;
+use crate::scene::BloomSettings;
+use crate::shader::Shader;
+
+/// Number of progressively halved blur targets, starting at half the screen size.
+const BLOOM_LEVELS: usize = 6;
+
+/// Glow around bright pixels of the HDR scene.
+///
+/// The bright parts of the scene are downsampled through a mip chain with a 13 tap filter, then upsampled back
+/// with a tent filter, adding each level into the one above it, as in Jimenez's "Next Generation Post Processing
+/// in Call of Duty: Advanced Warfare". The result is added to the scene colour before tone mapping.
+pub struct Bloom{
+    downsample_shader: Shader,
+    upsample_shader: Shader,
+    levels: Vec<Framebuffer>,
+}
+
+impl Bloom{
+    pub fn new(width: i32, height: i32) -> Bloom{
+        Bloom{
+            downsample_shader: Shader::new("shaders/postpro.vert", "shaders/bloom_downsample.frag"),
+            upsample_shader: Shader::new("shaders/postpro.vert", "shaders/bloom_upsample.frag"),
+            levels: levels(width, height),
+        }
+    }
+
+    /// Recreates the blur targets for a new screen size.
+    pub fn resize(&mut self, width: i32, height: i32){
+        self.levels = levels(width, height);
+    }
+
+    /// Blurs the pixels of the HDR colour texture `scene` that are brighter than the threshold.
+    ///
+    /// # Safety
+    /// The OpenGL context the bloom was created in must be current, `scene` must be a texture in it and
+    /// `quad_vao` must hold a fullscreen quad laid out for `shaders/postpro.vert`.
+    pub unsafe fn render(&self, scene: u32, scene_width: i32, scene_height: i32, quad_vao: u32, settings: &BloomSettings){
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+        gl::BindVertexArray(quad_vao);
+        gl::ActiveTexture(gl::TEXTURE0);
+
+        let shader = &self.downsample_shader;
+        shader.useProgram();
+        shader.setInt("source", 0);
+        shader.setFloat("threshold", settings.threshold);
+        let mut source = (scene, scene_width, scene_height);
+        for (i, level) in self.levels.iter().enumerate(){
+            // Only the first pass keeps just the bright pixels
+            shader.setInt("bright_pass", (i == 0) as i32);
+            shader.setUniform2f("texel_size", (1.0 / source.1 as f32, 1.0 / source.2 as f32));
+            level.bind();
+            gl::BindTexture(gl::TEXTURE_2D, source.0);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            source = (level.texture, level.width, level.height);
+        }
+
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::ONE, gl::ONE);
+        let shader = &self.upsample_shader;
+        shader.useProgram();
+        shader.setInt("source", 0);
+        shader.setFloat("radius", settings.radius);
+        for pair in self.levels.windows(2).rev(){
+            let (target, source) = (&pair[0], &pair[1]);
+            shader.setUniform2f("texel_size", (1.0 / source.width as f32, 1.0 / source.height as f32));
+            target.bind();
+            gl::BindTexture(gl::TEXTURE_2D, source.texture);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+        }
+
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        gl::BindVertexArray(0);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+
+    /// Blurred bright pixels at half the screen size, valid after `render`.
+    pub fn texture(&self) -> u32{
+        self.levels[0].texture
+    }
+}
+
+fn levels(width: i32, height: i32) -> Vec<Framebuffer>{
+    (1..=BLOOM_LEVELS).map(|i| {
+        let level = Framebuffer::new((width >> i).max(1), (height >> i).max(1), gl::R11F_G11F_B10F);
+        unsafe {
+            // Repeating would bleed light from one edge of the screen to the other
+            gl::BindTexture(gl::TEXTURE_2D, level.texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        level
+    }).collect()
+}
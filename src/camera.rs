@@ -1,10 +1,56 @@
 use cgmath::prelude::*;
-use cgmath::{Point3, Vector3, vec3, Matrix4};
+use cgmath::{Deg, Point3, Vector3, vec3, Matrix4, Rad, ortho, perspective};
 
 const CAM_FRONT: Vector3<f32> = vec3(0.0, 0.0, -1.0);
 const CAM_POS: Point3<f32> = Point3::new(0.0, 0.0, 0.0);
 const YAW: f32 = 270.0;
 const PITCH: f32 = 0.0;
+/// Distance to the target when orbiting starts from fly mode.
+const ORBIT_DISTANCE: f32 = 5.0;
+/// Closest an orbiting camera gets to its target.
+const MIN_DISTANCE: f32 = 0.1;
+/// Factor the orbit distance changes by per step of the scroll wheel.
+const ZOOM_STEP: f32 = 0.9;
+/// Pan per unit of mouse movement, as a fraction of the orbit distance.
+const PAN_SPEED: f32 = 0.002;
+const FOV: Deg<f32> = Deg(45.0);
+/// Degrees the field of view narrows by per step of the scroll wheel when flying.
+const FOV_STEP: f32 = 2.0;
+const MIN_FOV: f32 = 10.0;
+const MAX_FOV: f32 = 90.0;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection{
+    Perspective,
+    /// Parallel projection as tall as the perspective view is at the orbit distance, so switching keeps the
+    /// target the same size and zooming still works.
+    Orthographic,
+    /// Perspective with no far plane and depth running from 1 at the near plane to 0 at infinity, drawn with
+    /// the depth test reversed. Without `glClipControl`, which GL 3.3 lacks, this mostly buys the infinite range
+    /// rather than better precision.
+    ReversedZ,
+}
+
+impl Projection{
+    /// The projection after this one, going back to the first after the last.
+    pub fn next(self) -> Projection{
+        match self{
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::ReversedZ,
+            Projection::ReversedZ => Projection::Perspective,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMode{
+    /// Free flight: the mouse turns the camera where it stands and the keys move it.
+    Fly,
+    /// The mouse circles the camera around its target, at a distance changed by zooming.
+    Orbit,
+}
 
 pub enum Direction {
     Forward,
@@ -16,10 +62,23 @@ pub enum Direction {
 pub struct Camera{
     pub pos: Point3<f32>,
     pub front: Vector3<f32>,
+    /// Point the camera circles in orbit mode, `distance` in front of it.
+    pub target: Point3<f32>,
+    pub projection: Projection,
+    /// Vertical field of view.
+    pub fov: Deg<f32>,
+    /// Width over height of the view.
+    pub aspect: f32,
+    /// Near and far planes. Reversed-Z doesn't clip at `far`, but it still bounds the depths divided into light
+    /// clusters and shadow cascades.
+    pub near: f32,
+    pub far: f32,
     
     up: Vector3<f32>,
     yaw: f32,
     pitch: f32,
+    mode: CameraMode,
+    distance: f32,
 }
 
 impl Camera{
@@ -29,40 +88,229 @@ impl Camera{
             front: CAM_FRONT,
             up: vec3(0.0, 1.0, 0.0),
             yaw: YAW,
-            pitch: PITCH
+            pitch: PITCH,
+            target: CAM_POS + CAM_FRONT * ORBIT_DISTANCE,
+            projection: Projection::Perspective,
+            fov: FOV,
+            aspect: 800.0 / 600.0,
+            near: NEAR,
+            far: FAR,
+            mode: CameraMode::Fly,
+            distance: ORBIT_DISTANCE,
         }
     }
 
+    pub fn mode(&self) -> CameraMode{
+        self.mode
+    }
+
+    /// Switches between flying and orbiting without moving the camera. Orbiting circles the point
+    /// `ORBIT_DISTANCE` ahead.
+    pub fn set_mode(&mut self, mode: CameraMode){
+        if mode == CameraMode::Orbit && self.mode == CameraMode::Fly {
+            self.distance = ORBIT_DISTANCE;
+            self.target = self.pos + self.front * self.distance;
+        }
+        self.mode = mode;
+    }
+
+    /// Creates a camera at `pos` facing along `yaw` and `pitch` in degrees.
+    pub fn at(pos: Point3<f32>, yaw: f32, pitch: f32) -> Camera{
+        let mut camera = Camera::new();
+        camera.pos = pos;
+        camera.turn(yaw - YAW, pitch - PITCH);
+        camera
+    }
+
     pub fn get_view(&self) -> Matrix4<f32>{
-        return Matrix4::look_at(self.pos, self.pos+self.front, self.up);
+        Matrix4::look_at(self.pos, self.pos+self.front, self.up)
     }
 
+    pub fn get_projection(&self) -> Matrix4<f32>{
+        match self.projection{
+            Projection::Perspective => perspective(self.fov, self.aspect, self.near, self.far),
+            Projection::Orthographic => self.projection_between(self.near, self.far),
+            Projection::ReversedZ => {
+                // Clip space z is 2 * near - distance, so depth is near / distance
+                let f = 1.0 / (Rad::from(self.fov).0 / 2.0).tan();
+                Matrix4::new(
+                    f / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, 1.0, -1.0,
+                    0.0, 0.0, 2.0 * self.near, 0.0,
+                )
+            },
+        }
+    }
+
+    pub fn get_view_projection(&self) -> Matrix4<f32>{
+        self.get_projection() * self.get_view()
+    }
+
+    /// The projection's view volume cut down to the depths from `near` to `far`, with ordinary depth. Used to
+    /// fit shadow cascades to slices of the view.
+    pub fn projection_between(&self, near: f32, far: f32) -> Matrix4<f32>{
+        match self.projection{
+            Projection::Perspective | Projection::ReversedZ => perspective(self.fov, self.aspect, near, far),
+            Projection::Orthographic => {
+                let half_height = self.distance * (Rad::from(self.fov).0 / 2.0).tan();
+                let half_width = half_height * self.aspect;
+                ortho(-half_width, half_width, -half_height, half_height, near, far)
+            },
+        }
+    }
+
+    /// Whether nearer surfaces have greater depth, so the depth test and clear value must be flipped.
+    pub fn reversed_z(&self) -> bool{
+        self.projection == Projection::ReversedZ
+    }
+
+    /// Moves the camera, and in orbit mode its target along with it.
     pub fn translate(&mut self, dir: Direction, delta_time: &f32){
         let camera_speed: f32 = 5.0*delta_time;
 
-        match dir{
-            Direction::Forward => self.pos += camera_speed * self.front,
-            Direction::Backward => self.pos -= camera_speed * self.front,
-            Direction::Left => self.pos -= camera_speed * self.front.cross(self.up).normalize(),
-            Direction::Right => self.pos += camera_speed * self.front.cross(self.up).normalize(),
+        let offset = match dir{
+            Direction::Forward => camera_speed * self.front,
+            Direction::Backward => -camera_speed * self.front,
+            Direction::Left => -camera_speed * self.front.cross(self.up).normalize(),
+            Direction::Right => camera_speed * self.front.cross(self.up).normalize(),
+        };
+        self.pos += offset;
+        self.target += offset;
+    }
+
+    /// Moves the camera and its target across the view, so the scene follows a mouse moved `x` right and `y` up.
+    /// Steps grow with the orbit distance.
+    pub fn pan(&mut self, x: f32, y: f32){
+        let right = self.front.cross(self.up).normalize();
+        let up = right.cross(self.front);
+        let offset = (right * -x + up * -y) * self.distance * PAN_SPEED;
+        self.pos += offset;
+        self.target += offset;
+    }
+
+    /// Zooms in by `steps` of the scroll wheel, or out for negative steps: an orbiting camera moves towards its
+    /// target, a flying one narrows its field of view.
+    pub fn zoom(&mut self, steps: f32){
+        match self.mode{
+            CameraMode::Orbit => {
+                self.distance = (self.distance * ZOOM_STEP.powf(steps)).max(MIN_DISTANCE);
+                self.pos = self.target - self.front * self.distance;
+            },
+            CameraMode::Fly => self.fov = Deg((self.fov.0 - steps * FOV_STEP).clamp(MIN_FOV, MAX_FOV)),
         }
     }
 
+    /// Orbits the centre of the box from `min` to `max`, just far enough away for it to fill the view.
+    pub fn frame(&mut self, min: Point3<f32>, max: Point3<f32>){
+        let radius = (max - min).magnitude() / 2.0;
+        // The box's bounding sphere has to fit the narrower of the vertical and horizontal fields of view
+        let fovy = Rad::from(self.fov).0;
+        let fovx = 2.0 * ((fovy / 2.0).tan() * self.aspect).atan();
+        self.mode = CameraMode::Orbit;
+        self.target = min.midpoint(max);
+        self.distance = (radius / (fovy.min(fovx) / 2.0).sin()).max(MIN_DISTANCE);
+        self.pos = self.target - self.front * self.distance;
+    }
+
     pub fn turn(&mut self, yaw: f32, pitch: f32){
         self.yaw += yaw;
         self.pitch += pitch;
 
-        if self.pitch > 89.0{
-            self.pitch = 89.0;
-        }
-        if self.pitch < -89.0{
-            self.pitch = -89.0;
-        }
+        self.pitch = self.pitch.clamp(-89.0, 89.0);
 
         self.front = Vector3{
             x: self.yaw.to_radians().cos() * self.pitch.to_radians().cos(),
             y: self.pitch.to_radians().sin(),
             z: self.yaw.to_radians().sin() * self.pitch.to_radians().cos(),
         };
+
+        if self.mode == CameraMode::Orbit {
+            self.pos = self.target - self.front * self.distance;
+        }
+    }
+}
+
+impl Default for Camera{
+    fn default() -> Self{
+        Camera::new()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use cgmath::{Transform, Vector4};
+
+    fn assert_close(a: Point3<f32>, b: Point3<f32>){
+        assert!(a.distance(b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn orbiting_keeps_the_target_in_view(){
+        let mut camera = Camera::at(Point3::new(0.0, 0.0, 5.0), 270.0, 0.0);
+        camera.set_mode(CameraMode::Orbit);
+        assert_close(camera.target, Point3::new(0.0, 0.0, 0.0));
+
+        camera.turn(90.0, 30.0);
+        assert!((camera.pos.distance(camera.target) - ORBIT_DISTANCE).abs() < 1e-4);
+        assert_close(camera.pos + camera.front * ORBIT_DISTANCE, camera.target);
+
+        camera.zoom(1.0);
+        assert!((camera.pos.distance(camera.target) - ORBIT_DISTANCE * ZOOM_STEP).abs() < 1e-4);
+
+        // Flying again leaves the camera where it was
+        let pos = camera.pos;
+        camera.set_mode(CameraMode::Fly);
+        camera.zoom(1.0);
+        assert_close(camera.pos, pos);
+    }
+
+    #[test]
+    fn framing_fits_the_box_in_view(){
+        let mut camera = Camera::new();
+        let fovy = Rad::from(camera.fov);
+        camera.frame(Point3::new(-1.0, -1.0, -1.0), Point3::new(3.0, 1.0, 1.0));
+
+        assert_eq!(camera.mode(), CameraMode::Orbit);
+        assert_close(camera.target, Point3::new(1.0, 0.0, 0.0));
+        // The box's bounding sphere touches the edges of the view
+        let radius = 6.0f32.sqrt();
+        assert!((camera.pos.distance(camera.target) * (fovy.0 / 2.0).sin() - radius).abs() < 1e-4);
+    }
+
+    #[test]
+    fn framing_fits_the_box_in_a_narrow_view(){
+        let mut camera = Camera::new();
+        camera.aspect = 0.5;
+        let fovx = 2.0 * ((Rad::from(camera.fov).0 / 2.0).tan() * camera.aspect).atan();
+        camera.frame(Point3::new(-1.0, -1.0, -1.0), Point3::new(3.0, 1.0, 1.0));
+
+        // Taller than wide, so the sphere touches the left and right edges
+        let radius = 6.0f32.sqrt();
+        assert!((camera.pos.distance(camera.target) * (fovx / 2.0).sin() - radius).abs() < 1e-4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reversed_z_puts_the_near_plane_at_one_and_infinity_at_zero(){
+        let mut camera = Camera::new();
+        camera.projection = Projection::ReversedZ;
+        let projection = camera.get_projection();
+        let depth = |distance: f32| 0.5 * projection.transform_point(Point3::new(0.0, 0.0, -distance)).z + 0.5;
+
+        assert!((depth(camera.near) - 1.0).abs() < 1e-5);
+        assert!(depth(1.0) > depth(10.0) && depth(10.0) > depth(1000.0) && depth(1000.0) > 0.0);
+        let infinity = projection * Vector4::new(0.0, 0.0, -1.0, 0.0);
+        assert!((infinity.z / infinity.w + 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn scrolling_narrows_the_field_of_view_when_flying(){
+        let mut camera = Camera::new();
+        camera.zoom(2.0);
+        assert_eq!(camera.fov, Deg(FOV.0 - 2.0 * FOV_STEP));
+
+        camera.zoom(-100.0);
+        assert_eq!(camera.fov, Deg(MAX_FOV));
+    }
+}
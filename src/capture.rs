@@ -0,0 +1,167 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+
+use gl;
+use image;
+
+/// Pixels read back from a render target, stored top row first.
+pub enum Pixels {
+    /// 8-bit RGB, saved as PNG.
+    Ldr(Vec<u8>),
+    /// Floating point RGB, saved as EXR.
+    Hdr(Vec<f32>),
+    /// Single channel depth, saved as EXR.
+    Depth(Vec<f32>),
+}
+
+pub struct Capture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Pixels,
+}
+
+impl Capture {
+    /// Reads the first colour attachment of `fbo` with `glReadPixels`.
+    ///
+    /// # Safety
+    /// Needs a current OpenGL context in which `fbo` is a complete framebuffer at least `width` by `height`.
+    pub unsafe fn read_color(fbo: u32, width: u32, height: u32, hdr: bool) -> Capture{
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+        gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+
+        let len = (width * height * 3) as usize;
+        let pixels = if hdr {
+            let mut data = vec![0.0f32; len];
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGB, gl::FLOAT, data.as_mut_ptr() as *mut c_void);
+            Pixels::Hdr(flip_rows(&data, (width * 3) as usize))
+        } else {
+            let mut data = vec![0u8; len];
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGB, gl::UNSIGNED_BYTE, data.as_mut_ptr() as *mut c_void);
+            Pixels::Ldr(flip_rows(&data, (width * 3) as usize))
+        };
+
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+
+        Capture { width, height, pixels }
+    }
+
+    /// Reads level 0 of every layer of a depth texture array such as the shadow cascades, stacked with the first
+    /// layer at the top.
+    ///
+    /// # Safety
+    /// Needs a current OpenGL context in which `texture` is a depth array texture of that size with at least
+    /// `layers` layers.
+    pub unsafe fn read_depth_array(texture: u32, width: u32, height: u32, layers: u32) -> Capture{
+        let layer_len = (width * height) as usize;
+        let mut data = vec![0.0f32; layer_len * layers as usize];
+
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::GetTexImage(gl::TEXTURE_2D_ARRAY, 0, gl::DEPTH_COMPONENT, gl::FLOAT, data.as_mut_ptr() as *mut c_void);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+
+        let pixels = data.chunks(layer_len).flat_map(|layer| flip_rows(layer, width as usize)).collect();
+        Capture { width, height: height * layers, pixels: Pixels::Depth(pixels) }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self.pixels {
+            Pixels::Ldr(_) => "png",
+            Pixels::Hdr(_) | Pixels::Depth(_) => "exr",
+        }
+    }
+
+    /// Saves the capture, replacing the extension of `path` with the one matching the pixel format.
+    /// Returns the path that was written.
+    pub fn save(&self, path: &Path) -> io::Result<PathBuf>{
+        let path = path.with_extension(self.extension());
+
+        match &self.pixels {
+            Pixels::Ldr(data) => {
+                image::save_buffer(&path, data, self.width, self.height, image::ColorType::RGB(8))
+                    .map_err(io::Error::other)?;
+            },
+            Pixels::Hdr(data) => {
+                let channel = |offset: usize| data.iter().skip(offset).step_by(3).cloned().collect::<Vec<f32>>();
+                let (r, g, b) = (channel(0), channel(1), channel(2));
+                write_exr(&path, self.width, self.height, &[("B", &b), ("G", &g), ("R", &r)])?;
+            },
+            Pixels::Depth(data) => {
+                write_exr(&path, self.width, self.height, &[("Y", data)])?;
+            },
+        }
+
+        Ok(path)
+    }
+}
+
+fn flip_rows<T: Copy>(data: &[T], row_len: usize) -> Vec<T>{
+    data.chunks(row_len).rev().flatten().cloned().collect()
+}
+
+/// Writes an uncompressed scanline OpenEXR file with 32-bit float channels.
+/// Channels hold one value per pixel, top row first, and must be sorted by name as the format requires.
+fn write_exr(path: &Path, width: u32, height: u32, channels: &[(&str, &[f32])]) -> io::Result<()>{
+    fn attribute(out: &mut Vec<u8>, name: &str, type_: &str, value: &[u8]){
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+        out.extend_from_slice(type_.as_bytes());
+        out.push(0);
+        out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        out.extend_from_slice(value);
+    }
+
+    fn box2i(width: u32, height: u32) -> Vec<u8>{
+        [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v| v.to_le_bytes().to_vec()).collect()
+    }
+
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+
+    let mut channel_list = Vec::new();
+    for (name, _) in channels {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        channel_list.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "displayWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    let line_size = (width as usize) * channels.len() * 4;
+    let first_line = header.len() + height as usize * 8;
+
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&header)?;
+
+    for y in 0..height as usize {
+        let offset = first_line + y * (line_size + 8);
+        out.write_all(&(offset as u64).to_le_bytes())?;
+    }
+
+    for y in 0..height as usize {
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(line_size as i32).to_le_bytes())?;
+
+        for (_, data) in channels {
+            for value in &data[y * width as usize..(y + 1) * width as usize] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+
+    out.flush()
+}
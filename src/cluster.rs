@@ -0,0 +1,246 @@
+use std::mem;
+use std::os::raw::c_void;
+
+use cgmath::{Matrix4, Vector3, Vector4, vec3};
+use cgmath::prelude::*;
+use gl;
+use gl::types::*;
+
+use crate::light::PointLight;
+
+/// Number of clusters across, down and into the view frustum.
+pub const CLUSTER_GRID: [usize; 3] = [16, 9, 24];
+
+/// Texels per point light in the light data buffer, see `PointLight::texels`.
+const TEXELS_PER_LIGHT: usize = 6;
+
+/// Per cluster light lists in the layout read by `shaders/shader.frag`.
+pub struct ClusterAssignment{
+    /// Offset into `indices` and number of lights, for each cluster in x, then y, then z order.
+    pub clusters: Vec<[u32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+/// Depth of the boundary in front of slice `k`. Slices are spaced logarithmically so clusters stay roughly cubic.
+fn slice_depth(k: usize, near: f32, far: f32) -> f32{
+    near * (far / near).powf(k as f32 / CLUSTER_GRID[2] as f32)
+}
+
+fn slice_of(depth: f32, near: f32, far: f32) -> usize{
+    let k = ((depth / near).ln() / (far / near).ln() * CLUSTER_GRID[2] as f32).floor();
+    (k.max(0.0) as usize).min(CLUSTER_GRID[2] - 1)
+}
+
+/// Assigns point lights to the clusters their range overlaps, on the CPU.
+/// `view` and `projection` are the camera matrices; `near` and `far` bound the slices and should match the projection.
+pub fn assign_lights(lights: &[PointLight], view: &Matrix4<f32>, projection: &Matrix4<f32>, near: f32, far: f32) -> ClusterAssignment{
+    let [nx, ny, nz] = CLUSTER_GRID;
+    let inverse_projection = projection.invert().unwrap_or_else(Matrix4::identity);
+
+    // View space lines through the corners of every tile, as the point at depth 0 and the step per unit of depth.
+    // They are found from two depths inside every projection's range, so perspective, orthographic and
+    // reversed-Z projections all work
+    let corner = |i: usize, j: usize| -> (Vector3<f32>, Vector3<f32>) {
+        let unproject = |z: f32| {
+            let p = inverse_projection * Vector4::new(-1.0 + 2.0 * i as f32 / nx as f32, -1.0 + 2.0 * j as f32 / ny as f32, z, 1.0);
+            p.truncate() / p.w
+        };
+        let (a, b) = (unproject(0.0), unproject(0.5));
+        let step = (b - a) / (a.z - b.z);
+        (a + step * a.z, step)
+    };
+    let corners: Vec<(Vector3<f32>, Vector3<f32>)> = (0..=ny).flat_map(|j| (0..=nx).map(move |i| (i, j))).map(|(i, j)| corner(i, j)).collect();
+    let line = |i: usize, j: usize| corners[i + (nx + 1) * j];
+
+    let mut lists: Vec<Vec<u32>> = vec!(vec!(); nx * ny * nz);
+
+    for (index, light) in lights.iter().enumerate(){
+        let centre = (view * light.position.extend(1.0)).truncate();
+        let radius = light.range();
+        let depth = -centre.z;
+
+        if depth + radius < near || depth - radius > far {
+            continue;
+        }
+
+        for k in slice_of(depth - radius, near, far)..=slice_of(depth + radius, near, far){
+            let (front, back) = (slice_depth(k, near, far), slice_depth(k + 1, near, far));
+
+            for j in 0..ny{
+                for i in 0..nx{
+                    let lines = [line(i, j), line(i + 1, j), line(i, j + 1), line(i + 1, j + 1)];
+                    let mut min = vec3(f32::MAX, f32::MAX, -back);
+                    let mut max = vec3(f32::MIN, f32::MIN, -front);
+                    for &(base, step) in lines.iter(){
+                        for &d in [front, back].iter(){
+                            let p = base + step * d;
+                            min.x = min.x.min(p.x);
+                            min.y = min.y.min(p.y);
+                            max.x = max.x.max(p.x);
+                            max.y = max.y.max(p.y);
+                        }
+                    }
+
+                    let closest = vec3(
+                        centre.x.max(min.x).min(max.x),
+                        centre.y.max(min.y).min(max.y),
+                        centre.z.max(min.z).min(max.z),
+                    );
+                    if (closest - centre).magnitude2() <= radius * radius {
+                        lists[i + nx * (j + ny * k)].push(index as u32);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut assignment = ClusterAssignment{ clusters: Vec::with_capacity(lists.len()), indices: vec!() };
+    for list in lists{
+        assignment.clusters.push([assignment.indices.len() as u32, list.len() as u32]);
+        assignment.indices.extend(list);
+    }
+    assignment
+}
+
+/// Texture buffers holding point light data and the cluster light lists for the lit shader.
+pub struct Clusters{
+    light_buffer: u32,
+    light_texture: u32,
+    cluster_buffer: u32,
+    cluster_texture: u32,
+    index_buffer: u32,
+    index_texture: u32,
+}
+
+impl Clusters{
+    pub fn new() -> Clusters{
+        let mut clusters = Clusters{
+            light_buffer: 0, light_texture: 0,
+            cluster_buffer: 0, cluster_texture: 0,
+            index_buffer: 0, index_texture: 0,
+        };
+
+        unsafe {
+            for (buffer, texture, format) in [
+                (&mut clusters.light_buffer, &mut clusters.light_texture, gl::RGBA32F),
+                (&mut clusters.cluster_buffer, &mut clusters.cluster_texture, gl::RG32UI),
+                (&mut clusters.index_buffer, &mut clusters.index_texture, gl::R32UI),
+            ].iter_mut(){
+                gl::GenBuffers(1, &mut **buffer);
+                gl::GenTextures(1, &mut **texture);
+                gl::BindTexture(gl::TEXTURE_BUFFER, **texture);
+                gl::BindBuffer(gl::TEXTURE_BUFFER, **buffer);
+                gl::TexBuffer(gl::TEXTURE_BUFFER, *format, **buffer);
+            }
+            gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+            gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+        }
+
+        clusters
+    }
+
+    /// Uploads `lights` and their cluster assignment for this frame.
+    pub fn update(&mut self, lights: &[PointLight], view: &Matrix4<f32>, projection: &Matrix4<f32>, near: f32, far: f32){
+        let assignment = assign_lights(lights, view, projection, near, far);
+
+        let mut texels: Vec<[f32; 4]> = Vec::with_capacity(lights.len() * TEXELS_PER_LIGHT);
+        for light in lights{
+            texels.extend_from_slice(&light.texels());
+        }
+
+        unsafe {
+            upload(self.light_buffer, &texels);
+            upload(self.cluster_buffer, &assignment.clusters);
+            upload(self.index_buffer, &assignment.indices);
+        }
+    }
+
+    /// Binds the light data, cluster table and light index buffers to `first_unit` and the two units after it.
+    ///
+    /// # Safety
+    /// The OpenGL context the clusters were created in must be current.
+    pub unsafe fn bind(&self, first_unit: u32){
+        for (i, &texture) in [self.light_texture, self.cluster_texture, self.index_texture].iter().enumerate(){
+            gl::ActiveTexture(gl::TEXTURE0 + first_unit + i as u32);
+            gl::BindTexture(gl::TEXTURE_BUFFER, texture);
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+impl Default for Clusters{
+    fn default() -> Self{
+        Clusters::new()
+    }
+}
+
+/// Replaces the contents of a buffer. Empty data still allocates one element so the texture stays complete.
+unsafe fn upload<T>(buffer: u32, data: &[T]){
+    let size = (data.len().max(1) * mem::size_of::<T>()) as GLsizeiptr;
+    gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+    gl::BufferData(gl::TEXTURE_BUFFER, size, std::ptr::null(), gl::STREAM_DRAW);
+    gl::BufferSubData(gl::TEXTURE_BUFFER, 0, mem::size_of_val(data) as GLsizeiptr, data.as_ptr() as *const c_void);
+    gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+}
+
+impl Drop for Clusters{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteTextures(1, &self.light_texture);
+            gl::DeleteTextures(1, &self.cluster_texture);
+            gl::DeleteTextures(1, &self.index_texture);
+            gl::DeleteBuffers(1, &self.light_buffer);
+            gl::DeleteBuffers(1, &self.cluster_buffer);
+            gl::DeleteBuffers(1, &self.index_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use cgmath::{Deg, Point3, ortho, perspective};
+    use crate::light::{Attenuation, ShadowBias};
+
+    fn light_at(position: Vector3<f32>, range: f32) -> PointLight{
+        let white = vec3(1.0, 1.0, 1.0);
+        PointLight{ position, ambient: white, diffuse: white, specular: white, attenuation: Attenuation::for_range(range), shadow_bias: ShadowBias::default() }
+    }
+
+    #[test]
+    fn lights_only_reach_nearby_clusters(){
+        let view = Matrix4::look_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), vec3(0.0, 1.0, 0.0));
+        let projection = perspective(Deg(45.0), 16.0 / 9.0, 0.1, 100.0);
+
+        let lights = [light_at(vec3(0.0, 0.0, -10.0), 1.0), light_at(vec3(0.0, 0.0, 10.0), 1.0)];
+        let range = lights[0].range();
+        let assignment = assign_lights(&lights, &view, &projection, 0.1, 100.0);
+
+        // The light behind the camera touches nothing
+        assert!(assignment.indices.iter().all(|&i| i == 0));
+
+        // The light in front is in the centre cluster at its depth, but not in a corner one or the nearest slice
+        let [nx, ny, _] = CLUSTER_GRID;
+        let cluster = |i: usize, j: usize, k: usize| assignment.clusters[i + nx * (j + ny * k)];
+        let k = slice_of(10.0, 0.1, 100.0);
+        assert_eq!(cluster(nx / 2, ny / 2, k)[1], 1);
+        assert_eq!(cluster(0, 0, k)[1], 0);
+        assert_eq!(cluster(nx / 2, ny / 2, 0)[1], 0);
+        assert!(range > 1.0 && range < 10.0);
+    }
+
+    #[test]
+    fn orthographic_tiles_keep_their_width_with_depth(){
+        let view = Matrix4::look_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), vec3(0.0, 1.0, 0.0));
+        let projection = ortho(-10.0, 10.0, -10.0, 10.0, 0.1, 100.0);
+
+        let lights = [light_at(vec3(9.5, 0.0, -50.0), 0.2)];
+        let assignment = assign_lights(&lights, &view, &projection, 0.1, 100.0);
+
+        // Near the right edge at any depth, where a perspective tile that far out would be much wider
+        let [nx, ny, _] = CLUSTER_GRID;
+        let k = slice_of(50.0, 0.1, 100.0);
+        assert_eq!(assignment.clusters[nx - 1 + nx * (ny / 2 + ny * k)][1], 1);
+        assert_eq!(assignment.clusters[nx / 2 + nx * (ny / 2 + ny * k)][1], 0);
+    }
+}
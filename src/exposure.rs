@@ -0,0 +1,101 @@
+use gl;
+
+use crate::framebuffer::Framebuffer;
+use crate::shader::Shader;
+
+/// Width and height of the log luminance texture. A power of two, so its last mip level is one texel.
+const LUMINANCE_SIZE: i32 = 256;
+/// How quickly the adapted luminance follows the scene, per second, like an eye adjusting to the dark.
+const ADAPTATION_RATE: f32 = 1.5;
+
+/// Average scene luminance for automatic exposure, smoothed over time.
+///
+/// The scene is downsampled to a fixed size log luminance texture whose mip chain averages it on the GPU;
+/// the last level is then blended into a one texel adapted luminance target.
+pub struct AutoExposure{
+    luminance_shader: Shader,
+    adapt_shader: Shader,
+    luminance: Framebuffer,
+    /// Adapted luminance for this frame and the previous one, swapped every update.
+    adapted: [Framebuffer; 2],
+    current: usize,
+    /// Scene time of the last update, `None` until the first one.
+    last_time: Option<f32>,
+}
+
+impl AutoExposure{
+    pub fn new() -> AutoExposure{
+        let luminance = Framebuffer::new(LUMINANCE_SIZE, LUMINANCE_SIZE, gl::R16F);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, luminance.texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_NEAREST as i32);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        AutoExposure{
+            luminance_shader: Shader::new("shaders/postpro.vert", "shaders/luminance.frag"),
+            adapt_shader: Shader::new("shaders/postpro.vert", "shaders/adapt.frag"),
+            luminance,
+            adapted: [Framebuffer::new(1, 1, gl::R32F), Framebuffer::new(1, 1, gl::R32F)],
+            current: 0,
+            last_time: None,
+        }
+    }
+
+    /// Measures the HDR colour texture `scene` and adapts towards it. `time` is the scene time in seconds;
+    /// when it jumps backwards the adapted luminance snaps to the measured one.
+    ///
+    /// # Safety
+    /// The OpenGL context the targets were created in must be current, `scene` must be a texture in it and
+    /// `quad_vao` must hold a fullscreen quad laid out for `shaders/postpro.vert`.
+    pub unsafe fn update(&mut self, scene: u32, quad_vao: u32, time: f32){
+        let elapsed = self.last_time.map(|last| time - last).filter(|&dt| dt >= 0.0);
+        self.last_time = Some(time);
+
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+        gl::BindVertexArray(quad_vao);
+        gl::ActiveTexture(gl::TEXTURE0);
+
+        self.luminance.bind();
+        self.luminance_shader.useProgram();
+        self.luminance_shader.setInt("scene", 0);
+        gl::BindTexture(gl::TEXTURE_2D, scene);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        gl::BindTexture(gl::TEXTURE_2D, self.luminance.texture);
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+
+        let previous = self.current;
+        self.current = 1 - self.current;
+
+        self.adapted[self.current].bind();
+        self.adapt_shader.useProgram();
+        self.adapt_shader.setInt("luminance", 0);
+        self.adapt_shader.setInt("previous", 1);
+        self.adapt_shader.setFloat("last_level", (LUMINANCE_SIZE as f32).log2());
+        // Without a previous frame to blend from, start at the measured luminance
+        self.adapt_shader.setFloat("blend", elapsed.map_or(1.0, |dt| 1.0 - (-dt * ADAPTATION_RATE).exp()));
+        gl::ActiveTexture(gl::TEXTURE1);
+        gl::BindTexture(gl::TEXTURE_2D, self.adapted[previous].texture);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindVertexArray(0);
+        gl::Enable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+
+    /// One texel texture holding the adapted average luminance in its red channel.
+    pub fn texture(&self) -> u32{
+        self.adapted[self.current].texture
+    }
+}
+
+impl Default for AutoExposure{
+    fn default() -> Self{
+        AutoExposure::new()
+    }
+}
@@ -0,0 +1,213 @@
+use std::ptr;
+
+use gl;
+use gl::types::*;
+
+/// An OpenGL framebuffer object together with the attachments it owns.
+pub struct Framebuffer{
+    pub id: u32,
+    /// Colour texture, or the depth texture for depth-only framebuffers.
+    pub texture: u32,
+    pub width: i32,
+    pub height: i32,
+    /// Every colour texture of a multiple render target framebuffer, starting with `texture`.
+    pub textures: Vec<u32>,
+    /// Depth/stencil texture of framebuffers made with `with_depth_texture`, otherwise 0.
+    pub depth_texture: u32,
+
+    renderbuffer: u32,
+}
+
+impl Framebuffer{
+    /// Colour texture with a depth/stencil renderbuffer.
+    pub fn new(width: i32, height: i32, internal_format: GLenum) -> Framebuffer{
+        let mut fb = Framebuffer{ id: 0, texture: 0, depth_texture: 0, renderbuffer: 0, width, height, textures: vec!() };
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fb.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fb.id);
+
+            gl::GenTextures(1, &mut fb.texture);
+            gl::BindTexture(gl::TEXTURE_2D, fb.texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as i32, width, height, 0, gl::RGB, gl::UNSIGNED_BYTE, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, fb.texture, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenRenderbuffers(1, &mut fb.renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, fb.renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, fb.renderbuffer);
+
+            fb.check("colour");
+        }
+
+        fb
+    }
+
+    /// Colour texture with a depth/stencil texture, for passes that read the scene's depth.
+    pub fn with_depth_texture(width: i32, height: i32, internal_format: GLenum) -> Framebuffer{
+        let mut fb = Framebuffer{ id: 0, texture: 0, depth_texture: 0, renderbuffer: 0, width, height, textures: vec!() };
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fb.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fb.id);
+
+            gl::GenTextures(1, &mut fb.texture);
+            gl::BindTexture(gl::TEXTURE_2D, fb.texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as i32, width, height, 0, gl::RGB, gl::UNSIGNED_BYTE, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, fb.texture, 0);
+
+            gl::GenTextures(1, &mut fb.depth_texture);
+            gl::BindTexture(gl::TEXTURE_2D, fb.depth_texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH24_STENCIL8 as i32, width, height, 0, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::TEXTURE_2D, fb.depth_texture, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            fb.check("depth texture");
+        }
+
+        fb
+    }
+
+    /// Multisampled colour texture with a multisampled depth/stencil renderbuffer.
+    pub fn multisampled(width: i32, height: i32, samples: i32, internal_format: GLenum) -> Framebuffer{
+        let mut fb = Framebuffer{ id: 0, texture: 0, depth_texture: 0, renderbuffer: 0, width, height, textures: vec!() };
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fb.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fb.id);
+
+            gl::GenTextures(1, &mut fb.texture);
+            gl::BindTexture(gl::TEXTURE_2D_MULTISAMPLE, fb.texture);
+            gl::TexImage2DMultisample(gl::TEXTURE_2D_MULTISAMPLE, samples, internal_format, width, height, gl::TRUE);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D_MULTISAMPLE, fb.texture, 0);
+            gl::BindTexture(gl::TEXTURE_2D_MULTISAMPLE, 0);
+
+            gl::GenRenderbuffers(1, &mut fb.renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, fb.renderbuffer);
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, gl::DEPTH24_STENCIL8, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, fb.renderbuffer);
+
+            fb.check("multisampled");
+        }
+
+        fb
+    }
+
+    /// One floating point or 8 bit colour texture per entry of `internal_formats`, written by fragment shader outputs
+    /// 0, 1, 2..., with a depth/stencil renderbuffer. Used for the deferred shading G-buffer.
+    pub fn multiple(width: i32, height: i32, internal_formats: &[GLenum]) -> Framebuffer{
+        let mut fb = Framebuffer{ id: 0, texture: 0, depth_texture: 0, renderbuffer: 0, width, height, textures: vec!() };
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fb.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fb.id);
+
+            let mut draw_buffers = vec!();
+            for (i, &internal_format) in internal_formats.iter().enumerate(){
+                let attachment = gl::COLOR_ATTACHMENT0 + i as u32;
+                let mut texture = 0;
+                gl::GenTextures(1, &mut texture);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as i32, width, height, 0, gl::RGBA, gl::FLOAT, ptr::null());
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture, 0);
+
+                fb.textures.push(texture);
+                draw_buffers.push(attachment);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr());
+            fb.texture = fb.textures[0];
+
+            gl::GenRenderbuffers(1, &mut fb.renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, fb.renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, fb.renderbuffer);
+
+            fb.check("multiple render target");
+        }
+
+        fb
+    }
+
+    /// Binds the framebuffer and sets the viewport to cover it.
+    ///
+    /// # Safety
+    /// The OpenGL context the framebuffer was created in must be current.
+    pub unsafe fn bind(&self){
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        gl::Viewport(0, 0, self.width, self.height);
+    }
+
+    /// Copies the colour attachment into `target`, resolving multisampling. A target of 0 is the window.
+    /// Copies to a different size are filtered linearly; multisampled framebuffers can only be copied at their own size.
+    ///
+    /// # Safety
+    /// The OpenGL context the framebuffer was created in must be current and `target` must be a framebuffer in it.
+    pub unsafe fn blit(&self, target: u32, target_width: i32, target_height: i32){
+        let filter = if (target_width, target_height) == (self.width, self.height) { gl::NEAREST } else { gl::LINEAR };
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target);
+        gl::BlitFramebuffer(0, 0, self.width, self.height, 0, 0, target_width, target_height, gl::COLOR_BUFFER_BIT, filter);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+
+    /// Copies the depth buffer into `target`.
+    ///
+    /// # Safety
+    /// The OpenGL context the framebuffer was created in must be current and `target` must be a framebuffer in it
+    /// with the same size, sample count and depth format.
+    pub unsafe fn blit_depth(&self, target: u32){
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target);
+        gl::BlitFramebuffer(0, 0, self.width, self.height, 0, 0, self.width, self.height, gl::DEPTH_BUFFER_BIT, gl::NEAREST);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+
+    /// Whether the framebuffer can be drawn to. Incomplete framebuffers are also reported when they are made.
+    pub fn is_complete(&self) -> bool{
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status == gl::FRAMEBUFFER_COMPLETE
+        }
+    }
+
+    unsafe fn check(&self, kind: &str){
+        if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+            println!("ERROR::FRAMEBUFFER:: {} framebuffer is not complete!", kind);
+        }
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+}
+
+impl Drop for Framebuffer{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            if self.textures.is_empty() {
+                gl::DeleteTextures(1, &self.texture);
+            } else {
+                gl::DeleteTextures(self.textures.len() as i32, self.textures.as_ptr());
+            }
+            if self.depth_texture != 0 {
+                gl::DeleteTextures(1, &self.depth_texture);
+            }
+            if self.renderbuffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.renderbuffer);
+            }
+        }
+    }
+}
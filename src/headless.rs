@@ -0,0 +1,56 @@
+use std::os::raw::c_void;
+use std::ptr;
+
+use khronos_egl as egl;
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, a display with no window system behind it.
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+/// An OpenGL 3.3 core context made current without a window, display server or surface, through EGL's
+/// surfaceless platform. Everything is drawn into framebuffer objects, which is all headless mode needs.
+///
+/// libEGL is loaded at run time, so the binary still starts on machines without it as long as it isn't headless.
+/// Mesa provides the platform, on a GPU or on llvmpipe with `LIBGL_ALWAYS_SOFTWARE=1`.
+pub struct HeadlessContext{
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    context: egl::Context,
+}
+
+impl HeadlessContext{
+    pub fn new() -> Result<HeadlessContext, String>{
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|err| format!("failed to load libEGL 1.5: {}", err))?;
+        let display = egl.get_platform_display(PLATFORM_SURFACELESS_MESA, ptr::null_mut(), &[egl::ATTRIB_NONE])
+            .map_err(|err| format!("no surfaceless EGL display: {}", err))?;
+        egl.initialize(display).map_err(|err| format!("failed to initialise EGL: {}", err))?;
+
+        let config = egl.choose_first_config(display, &[egl::SURFACE_TYPE, egl::PBUFFER_BIT, egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::NONE])
+            .map_err(|err| format!("failed to choose an EGL config: {}", err))?
+            .ok_or("no EGL config supports OpenGL")?;
+        egl.bind_api(egl::OPENGL_API).map_err(|err| format!("failed to bind OpenGL: {}", err))?;
+        let context = egl.create_context(display, config, None, &[
+            egl::CONTEXT_MAJOR_VERSION, 3,
+            egl::CONTEXT_MINOR_VERSION, 3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ]).map_err(|err| format!("failed to create an OpenGL 3.3 core context: {}", err))?;
+        egl.make_current(display, None, None, Some(context)).map_err(|err| format!("failed to make the context current: {}", err))?;
+
+        Ok(HeadlessContext{ egl, display, context })
+    }
+
+    /// Address of an OpenGL function, for `gl::load_with`.
+    pub fn get_proc_address(&self, symbol: &str) -> *const c_void{
+        self.egl.get_proc_address(symbol).map_or(ptr::null(), |function| function as *const c_void)
+    }
+}
+
+impl Drop for HeadlessContext{
+    fn drop(&mut self){
+        // Nothing useful can be done about failures while shutting down
+        self.egl.make_current(self.display, None, None, None).ok();
+        self.egl.destroy_context(self.display, self.context).ok();
+        self.egl.terminate(self.display).ok();
+    }
+}
@@ -0,0 +1,159 @@
+use gl;
+
+use crate::shader::Shader;
+use crate::texture::CubeMap;
+
+/// Face size of the diffuse irradiance map. Irradiance changes slowly with direction, so this can be tiny.
+const IRRADIANCE_SIZE: i32 = 32;
+/// Face size of the base level of the prefiltered specular map.
+const PREFILTER_SIZE: i32 = 128;
+/// Mip levels of the prefiltered map, from roughness 0 at the base level to roughness 1 at the last.
+pub const PREFILTER_LEVELS: i32 = 5;
+/// Width and height of the BRDF integration lookup table.
+const BRDF_LUT_SIZE: i32 = 512;
+
+/// Ambient lighting precomputed from an environment cube map, sampled by the lit shaders.
+pub struct Environment{
+    /// Cosine weighted irradiance for each normal direction.
+    pub irradiance: CubeMap,
+    /// Radiance convolved with the GGX lobe, one roughness per mip level.
+    pub prefiltered: CubeMap,
+}
+
+impl Environment{
+    /// Convolves `source` on the GPU. Mipmaps are generated for `source` so bright, small features don't alias
+    /// into the rougher levels.
+    pub fn from_cube_map(source: &CubeMap) -> Environment{
+        let irradiance_shader = Shader::new("shaders/ibl.vert", "shaders/irradiance.frag");
+        let prefilter_shader = Shader::new("shaders/ibl.vert", "shaders/prefilter.frag");
+
+        let environment = Environment{
+            irradiance: CubeMap::empty(IRRADIANCE_SIZE, gl::RGB16F, 1),
+            prefiltered: CubeMap::empty(PREFILTER_SIZE, gl::RGB16F, PREFILTER_LEVELS),
+        };
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, source.id);
+            gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, source.id);
+
+            let target = FaceTarget::new();
+
+            irradiance_shader.useProgram();
+            irradiance_shader.setInt("environment", 0);
+            target.draw_faces(&irradiance_shader, &environment.irradiance, 0);
+
+            prefilter_shader.useProgram();
+            prefilter_shader.setInt("environment", 0);
+            prefilter_shader.setFloat("source_size", source.size as f32);
+            for level in 0..PREFILTER_LEVELS{
+                prefilter_shader.setFloat("roughness", level as f32 / (PREFILTER_LEVELS - 1) as f32);
+                target.draw_faces(&prefilter_shader, &environment.prefiltered, level);
+            }
+
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+
+        environment
+    }
+}
+
+/// Split sum scale and bias to apply to F0 for GGX specular, indexed by n·v and roughness.
+/// It doesn't depend on the environment, so one table serves every scene.
+pub struct BrdfLut{
+    pub texture: u32,
+}
+
+impl BrdfLut{
+    pub fn new() -> BrdfLut{
+        let shader = Shader::new("shaders/ibl.vert", "shaders/brdf.frag");
+        let mut lut = BrdfLut{ texture: 0 };
+
+        unsafe {
+            gl::GenTextures(1, &mut lut.texture);
+            gl::BindTexture(gl::TEXTURE_2D, lut.texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RG16F as i32, BRDF_LUT_SIZE, BRDF_LUT_SIZE, 0, gl::RG, gl::FLOAT, std::ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            let target = FaceTarget::new();
+            shader.useProgram();
+            shader.setInt("face", 0);
+            target.draw(gl::TEXTURE_2D, lut.texture, 0, BRDF_LUT_SIZE);
+        }
+
+        lut
+    }
+}
+
+impl Default for BrdfLut{
+    fn default() -> Self{
+        BrdfLut::new()
+    }
+}
+
+impl Drop for BrdfLut{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Framebuffer for drawing a fullscreen quad into one texture level at a time. `shaders/ibl.vert` builds the
+/// quad from the vertex id and passes on the cube map direction of each pixel for the face in `face`.
+pub(crate) struct FaceTarget{
+    fbo: u32,
+    vao: u32,
+}
+
+impl FaceTarget{
+    pub(crate) fn new() -> FaceTarget{
+        let mut target = FaceTarget{ fbo: 0, vao: 0 };
+        unsafe {
+            gl::GenFramebuffers(1, &mut target.fbo);
+            gl::GenVertexArrays(1, &mut target.vao);
+        }
+        target
+    }
+
+    /// Draws every face of `level` of `cube_map` with `shader`, which must be in use.
+    pub(crate) unsafe fn draw_faces(&self, shader: &Shader, cube_map: &CubeMap, level: i32){
+        let size = (cube_map.size >> level).max(1);
+        for face in 0..6{
+            shader.setInt("face", face as i32);
+            self.draw(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, cube_map.id, level, size);
+        }
+    }
+
+    pub(crate) unsafe fn draw(&self, texture_target: u32, texture: u32, level: i32, size: i32){
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, texture_target, texture, level);
+        gl::Viewport(0, 0, size, size);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+        gl::BindVertexArray(0);
+
+        gl::Enable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+}
+
+impl Drop for FaceTarget{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
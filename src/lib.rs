@@ -0,0 +1,27 @@
+//! Building blocks for rendering with OpenGL: shader programs, meshes, OBJ models with PBR materials, a fly
+//! and orbit camera, lights with cascaded, point and spot shadows, image based lighting, SSAO, TAA, bloom and
+//! a post-processing chain, plus framebuffer capture. A `Renderer` draws a `Scene` loaded from RON with them,
+//! through either a forward or a deferred pipeline.
+//!
+//! All types assume a current OpenGL context with function pointers loaded through `gl::load_with`.
+
+pub mod bloom;
+pub mod camera;
+pub mod capture;
+pub mod cluster;
+pub mod exposure;
+pub mod framebuffer;
+pub mod ibl;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod model;
+pub mod post_process;
+pub mod renderer;
+pub mod scene;
+pub mod scene_graph;
+pub mod shader;
+pub mod shadow;
+pub mod ssao;
+pub mod taa;
+pub mod texture;
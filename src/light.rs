@@ -0,0 +1,323 @@
+use cgmath::{Vector3, vec3};
+use serde::Deserialize;
+
+use crate::camera::Camera;
+use crate::shadow::SpotShadow;
+
+/// Array sizes of the `Lights` uniform block in `shaders/lighting.glsl`. Keep the two in sync.
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_SPOT_LIGHTS: usize = 16;
+/// Point lights live in a texture buffer and are culled per cluster, so this only guards against runaway scene files.
+pub const MAX_POINT_LIGHTS: usize = 1024;
+
+/// Intensity below which a point light no longer counts as reaching a cluster.
+const LIGHT_THRESHOLD: f32 = 1.0 / 256.0;
+
+/// Light falloff with distance `d`: `1 / (constant + linear * d + quadratic * d * d)`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation{
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation{
+    /// Falloff that fades out at roughly `range` units, using the widely copied Ogre3D fit.
+    pub fn for_range(range: f32) -> Attenuation{
+        Attenuation{
+            constant: 1.0,
+            linear: 4.5 / range,
+            quadratic: 75.0 / (range * range),
+        }
+    }
+}
+
+/// Keeps surfaces from shadowing themselves. Slope and normal offset are in shadow map texels, so they follow
+/// the resolution of whichever map or cascade is sampled.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct ShadowBias{
+    /// Depth bias in world units.
+    pub constant: f32,
+    /// Extra depth bias per texel, times the tangent of the angle between the surface normal and the light.
+    /// Surfaces the light grazes change depth fastest across a texel, so they need the most.
+    pub slope: f32,
+    /// Distance the lookup moves out along the surface normal before projecting into the shadow map.
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowBias{
+    fn default() -> Self{
+        ShadowBias{
+            constant: 0.02,
+            slope: 1.5,
+            normal_offset: 1.0,
+        }
+    }
+}
+
+impl ShadowBias{
+    fn pack(&self) -> [f32; 4]{
+        [self.constant, self.slope, self.normal_offset, 0.0]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight{
+    pub direction: Vector3<f32>,
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub shadow_bias: ShadowBias,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight{
+    pub position: Vector3<f32>,
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub attenuation: Attenuation,
+    pub shadow_bias: ShadowBias,
+}
+
+impl PointLight{
+    /// Light data in the texel layout `shaders/shader.frag` fetches from the point light buffer.
+    pub fn texels(&self) -> [[f32; 4]; 6]{
+        let a = self.attenuation;
+        [
+            self.position.extend(0.0).into(),
+            self.ambient.extend(0.0).into(),
+            self.diffuse.extend(0.0).into(),
+            self.specular.extend(0.0).into(),
+            [a.constant, a.linear, a.quadratic, 0.0],
+            self.shadow_bias.pack(),
+        ]
+    }
+
+    /// Distance at which the light's brightest colour has faded below `LIGHT_THRESHOLD`.
+    pub fn range(&self) -> f32{
+        let brightest = [self.ambient, self.diffuse, self.specular].iter()
+            .flat_map(|c| vec!(c.x, c.y, c.z))
+            .fold(0.0, f32::max);
+        let target = brightest / LIGHT_THRESHOLD;
+        let (c, l, q) = (self.attenuation.constant, self.attenuation.linear, self.attenuation.quadratic);
+
+        if target <= c {
+            0.0
+        } else if q > 0.0 {
+            (-l + (l * l - 4.0 * q * (c - target)).sqrt()) / (2.0 * q)
+        } else if l > 0.0 {
+            (target - c) / l
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotLight{
+    pub position: Vector3<f32>,
+    pub direction: Vector3<f32>,
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    /// Angle in degrees where the light starts to fade out.
+    pub cutoff: f32,
+    /// Angle in degrees where the light has faded out completely.
+    pub outer_cutoff: f32,
+    /// Ignore `position` and `direction` and shine from the camera, like a torch.
+    pub follow_camera: bool,
+    /// Render a shadow map for the light into the spot shadow atlas.
+    pub cast_shadows: bool,
+    pub shadow_bias: ShadowBias,
+}
+
+impl SpotLight{
+    /// Position and direction the light shines from, which follow `camera` for camera-attached lights.
+    pub fn placement(&self, camera: &Camera) -> (Vector3<f32>, Vector3<f32>){
+        if self.follow_camera {
+            (vec3(camera.pos.x, camera.pos.y, camera.pos.z), camera.front)
+        } else {
+            (self.position, self.direction)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light{
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+// The structs below mirror the std140 layout of the `Lights` block: every vec3 takes a full 16 byte slot.
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct DirectionalLightData{
+    direction: [f32; 4],
+    ambient: [f32; 4],
+    diffuse: [f32; 4],
+    specular: [f32; 4],
+    /// Constant, slope and normal offset terms of `ShadowBias`.
+    shadow_bias: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct SpotLightData{
+    position: [f32; 4],
+    direction: [f32; 4],
+    ambient: [f32; 4],
+    diffuse: [f32; 4],
+    specular: [f32; 4],
+    /// Cosines of the inner and outer cutoff angles.
+    cutoff: [f32; 4],
+    /// World space to the light's shadow clip space, column major.
+    shadow_transform: [[f32; 4]; 4],
+    /// Offset and scale of the light's tile in the spot shadow atlas, with w 1 when the light has a shadow map.
+    shadow_rect: [f32; 4],
+    shadow_bias: [f32; 4],
+}
+
+/// CPU copy of the `Lights` uniform block, uploaded as is.
+#[repr(C)]
+pub struct LightBlock{
+    /// Number of directional, point and spot lights in use.
+    counts: [i32; 4],
+    directional: [DirectionalLightData; MAX_DIRECTIONAL_LIGHTS],
+    spot: [SpotLightData; MAX_SPOT_LIGHTS],
+}
+
+/// Drops the lights beyond the maximum of their kind, keeping the first ones, and returns how many were dropped.
+/// `Scene::load` rejects files with that many, so this only happens to lights added while running. Everything that
+/// uploads or draws lights uses the limited list, so they all agree on which lights exist.
+pub fn limit_lights(lights: &mut Vec<Light>) -> usize{
+    let count = lights.len();
+    let (mut directional, mut point, mut spot) = (0, 0, 0);
+    lights.retain(|light| {
+        let (kept, max) = match light{
+            Light::Directional(_) => (&mut directional, MAX_DIRECTIONAL_LIGHTS),
+            Light::Point(_) => (&mut point, MAX_POINT_LIGHTS),
+            Light::Spot(_) => (&mut spot, MAX_SPOT_LIGHTS),
+        };
+        *kept += 1;
+        *kept <= max
+    });
+    count - lights.len()
+}
+
+fn vec4(v: Vector3<f32>) -> [f32; 4]{
+    [v.x, v.y, v.z, 0.0]
+}
+
+impl LightBlock{
+    pub fn new() -> Box<LightBlock>{
+        Box::new(LightBlock{
+            counts: [0; 4],
+            directional: [DirectionalLightData::default(); MAX_DIRECTIONAL_LIGHTS],
+            spot: [SpotLightData::default(); MAX_SPOT_LIGHTS],
+        })
+    }
+
+    /// Replaces the contents with `lights`. Point lights are only counted, their data goes to `Clusters`.
+    /// `spot_shadows` are the atlas tiles of the shadow casting spot lights, by their index among the spot lights.
+    ///
+    /// # Panics
+    ///
+    /// If there are more directional or spot lights than the block holds, see `limit_lights`.
+    pub fn pack<'a, I: IntoIterator<Item = &'a Light>>(&mut self, lights: I, camera: &Camera, spot_shadows: &[SpotShadow]){
+        let (mut directional, mut point, mut spot) = (0, 0usize, 0);
+
+        for light in lights{
+            match light{
+                Light::Directional(light) => {
+                    self.directional[directional] = DirectionalLightData{
+                        direction: vec4(light.direction),
+                        ambient: vec4(light.ambient),
+                        diffuse: vec4(light.diffuse),
+                        specular: vec4(light.specular),
+                        shadow_bias: light.shadow_bias.pack(),
+                    };
+                    directional += 1;
+                },
+                Light::Point(_) => point += 1,
+                Light::Spot(light) => {
+                    let (position, direction) = light.placement(camera);
+                    let shadow = spot_shadows.iter().find(|shadow| shadow.light == spot);
+                    self.spot[spot] = SpotLightData{
+                        position: vec4(position),
+                        direction: vec4(direction),
+                        ambient: vec4(light.ambient),
+                        diffuse: vec4(light.diffuse),
+                        specular: vec4(light.specular),
+                        cutoff: [light.cutoff.to_radians().cos(), light.outer_cutoff.to_radians().cos(), 0.0, 0.0],
+                        shadow_transform: shadow.map_or([[0.0; 4]; 4], |shadow| shadow.transform.into()),
+                        shadow_rect: shadow.map_or([0.0; 4], |shadow| shadow.rect),
+                        shadow_bias: light.shadow_bias.pack(),
+                    };
+                    spot += 1;
+                },
+            }
+        }
+
+        self.counts = [directional as i32, point as i32, spot as i32, 0];
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::mem;
+
+    #[test]
+    fn block_matches_std140_layout(){
+        assert_eq!(mem::size_of::<DirectionalLightData>(), 80);
+        assert_eq!(mem::size_of::<SpotLightData>(), 192);
+        assert_eq!(
+            mem::size_of::<LightBlock>(),
+            16 + 80 * MAX_DIRECTIONAL_LIGHTS + 192 * MAX_SPOT_LIGHTS
+        );
+    }
+
+    #[test]
+    fn pack_counts_each_kind(){
+        let white = vec3(1.0, 1.0, 1.0);
+        let point = Light::Point(PointLight{
+            position: vec3(1.0, 2.0, 3.0),
+            ambient: white, diffuse: white, specular: white,
+            attenuation: Attenuation::for_range(10.0),
+            shadow_bias: ShadowBias::default(),
+        });
+        let directional = Light::Directional(DirectionalLight{
+            direction: vec3(0.0, -1.0, 0.0),
+            ambient: white, diffuse: white, specular: white,
+            shadow_bias: ShadowBias::default(),
+        });
+
+        let mut lights = vec!(point; MAX_POINT_LIGHTS + 1);
+        lights.insert(0, directional);
+        assert_eq!(limit_lights(&mut lights), 1);
+
+        let mut block = LightBlock::new();
+        block.pack(lights.iter(), &Camera::new(), &[]);
+
+        assert_eq!(block.counts, [1, MAX_POINT_LIGHTS as i32, 0, 0]);
+    }
+
+    #[test]
+    fn limit_lights_keeps_the_first_of_each_kind(){
+        let white = vec3(1.0, 1.0, 1.0);
+        let directional = |y| Light::Directional(DirectionalLight{
+            direction: vec3(0.0, y, 0.0),
+            ambient: white, diffuse: white, specular: white,
+            shadow_bias: ShadowBias::default(),
+        });
+
+        let mut lights: Vec<Light> = (0..MAX_DIRECTIONAL_LIGHTS + 2).map(|i| directional(-(i as f32))).collect();
+        assert_eq!(limit_lights(&mut lights), 2);
+        assert_eq!(lights.len(), MAX_DIRECTIONAL_LIGHTS);
+        assert_eq!(lights.last(), Some(&directional(-((MAX_DIRECTIONAL_LIGHTS - 1) as f32))));
+    }
+}
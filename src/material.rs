@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use cgmath::{Vector3, vec3};
+use gl;
+
+use crate::mesh::Texture;
+use crate::shader::Shader;
+
+/// Metallic-roughness surface description. Each factor is multiplied with its map when one is present.
+#[derive(Clone)]
+pub struct Material{
+    pub base_colour: Vector3<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vector3<f32>,
+
+    /// sRGB colour.
+    pub base_colour_map: Option<Texture>,
+    /// Linear, read from the red channel.
+    pub metallic_map: Option<Texture>,
+    /// Linear, read from the red channel.
+    pub roughness_map: Option<Texture>,
+    /// Ambient occlusion, linear, read from the red channel.
+    pub ao_map: Option<Texture>,
+    /// sRGB colour.
+    pub emissive_map: Option<Texture>,
+}
+
+impl Default for Material{
+    fn default() -> Self{
+        Material{
+            base_colour: vec3(1.0, 1.0, 1.0),
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: vec3(0.0, 0.0, 0.0),
+            base_colour_map: None,
+            metallic_map: None,
+            roughness_map: None,
+            ao_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+/// Texture units used by `Material::bind`. Shaders keep other samplers above these.
+const UNITS: [(&str, &str); 5] = [
+    ("base_colour_map", "has_base_colour_map"),
+    ("metallic_map", "has_metallic_map"),
+    ("roughness_map", "has_roughness_map"),
+    ("ao_map", "has_ao_map"),
+    ("emissive_map", "has_emissive_map"),
+];
+
+fn floats(value: &str) -> Vec<f32>{
+    value.split_whitespace().filter_map(|v| v.parse().ok()).collect()
+}
+
+impl Material{
+    /// Reads the PBR extension to MTL (`Pr`, `Pm`, `Ke` and their `map_` textures), falling back to the classic
+    /// `Kd`/`map_Kd` for base colour and deriving roughness from the Blinn-Phong exponent `Ns` when `Pr` is missing.
+    /// `map_Ka` is used as the ambient occlusion map.
+    /// `load` is called with a texture path relative to the MTL file and whether it holds sRGB colour, and its
+    /// first error is returned.
+    pub fn from_mtl<E, F: FnMut(&str, bool) -> Result<Texture, E>>(material: &tobj::Material, mut load: F) -> Result<Material, E>{
+        let params: &HashMap<String, String> = &material.unknown_param;
+        let param = |name: &str| params.get(name).map(|v| floats(v)).filter(|v| !v.is_empty());
+        let mut map = |path: Option<&String>, srgb: bool| path.filter(|p| !p.is_empty()).map(|p| load(p, srgb)).transpose();
+
+        let roughness = match param("Pr"){
+            Some(v) => v[0],
+            // Matches the Beckmann distribution to Blinn-Phong with the same highlight, see Walter et al. 2007
+            None => (2.0 / (material.shininess + 2.0)).sqrt(),
+        };
+
+        Ok(Material{
+            base_colour: material.diffuse.into(),
+            metallic: param("Pm").map_or(0.0, |v| v[0]),
+            roughness: roughness.clamp(0.0, 1.0),
+            emissive: match param("Ke"){
+                Some(v) if v.len() >= 3 => vec3(v[0], v[1], v[2]),
+                _ => vec3(0.0, 0.0, 0.0),
+            },
+            base_colour_map: map(Some(&material.diffuse_texture), true)?,
+            metallic_map: map(params.get("map_Pm"), false)?,
+            roughness_map: map(params.get("map_Pr"), false)?,
+            ao_map: map(Some(&material.ambient_texture), false)?,
+            emissive_map: map(params.get("map_Ke"), true)?,
+        })
+    }
+
+    /// Sets the `material` uniforms and binds the maps to texture units 0 to 4.
+    ///
+    /// # Safety
+    /// `shader` must be in use in the current OpenGL context, the one the maps were loaded into.
+    pub unsafe fn bind(&self, shader: &Shader){
+        shader.setUniform3f("material.base_colour", (self.base_colour.x, self.base_colour.y, self.base_colour.z));
+        shader.setFloat("material.metallic", self.metallic);
+        shader.setFloat("material.roughness", self.roughness);
+        shader.setUniform3f("material.emissive", (self.emissive.x, self.emissive.y, self.emissive.z));
+
+        let maps = [&self.base_colour_map, &self.metallic_map, &self.roughness_map, &self.ao_map, &self.emissive_map];
+        for (unit, (map, (sampler, flag))) in maps.iter().zip(UNITS.iter()).enumerate(){
+            shader.setInt(&format!("material.{}", sampler), unit as i32);
+            shader.setInt(&format!("material.{}", flag), map.is_some() as i32);
+
+            gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            gl::BindTexture(gl::TEXTURE_2D, map.as_ref().map_or(0, |texture| texture.id));
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn texture(path: &str, srgb: bool) -> Result<Texture, String>{
+        Ok(Texture{ id: 0, srgb, path: path.into() })
+    }
+
+    #[test]
+    fn reads_pbr_parameters(){
+        let mut mtl = tobj::Material::empty();
+        mtl.diffuse = [0.5, 0.25, 1.0];
+        mtl.unknown_param.insert("Pr".into(), "0.3".into());
+        mtl.unknown_param.insert("Pm".into(), "1.0".into());
+        mtl.unknown_param.insert("Ke".into(), "1.0 0.5 0.0".into());
+        mtl.unknown_param.insert("map_Pr".into(), "rough.png".into());
+        mtl.diffuse_texture = "albedo.png".into();
+
+        let material = Material::from_mtl(&mtl, texture).unwrap();
+
+        assert_eq!(material.base_colour, vec3(0.5, 0.25, 1.0));
+        assert_eq!(material.roughness, 0.3);
+        assert_eq!(material.metallic, 1.0);
+        assert_eq!(material.emissive, vec3(1.0, 0.5, 0.0));
+        assert!(material.base_colour_map.unwrap().srgb);
+        assert!(!material.roughness_map.unwrap().srgb);
+        assert!(material.metallic_map.is_none());
+    }
+
+    #[test]
+    fn derives_roughness_from_shininess(){
+        let mut mtl = tobj::Material::empty();
+        mtl.shininess = 0.0;
+        assert_eq!(Material::from_mtl(&mtl, texture).unwrap().roughness, 1.0);
+
+        mtl.shininess = 198.0;
+        assert!((Material::from_mtl(&mtl, texture).unwrap().roughness - 0.1).abs() < 1e-6);
+    }
+}
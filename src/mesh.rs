@@ -5,9 +5,12 @@ use std::ptr;
 use cgmath::{ Vector3, Vector2 };
 use cgmath::prelude::*;
 use gl;
+use memoffset::offset_of;
 
+use crate::material::Material;
 use crate::shader::Shader;
 
+#[repr(C)]
 pub struct Vertex{
     pub position: Vector3<f32>,
     pub normal: Vector3<f32>,
@@ -27,14 +30,15 @@ impl Default for Vertex{
 #[derive(Clone)]
 pub struct Texture{
     pub id: u32,
-    pub type_: String,
+    /// Colour data decoded from sRGB when sampled, rather than linear data such as roughness.
+    pub srgb: bool,
     pub path: String,
 }
 
 pub struct Mesh{
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
-    pub textures: Vec<Texture>,
+    pub material: Material,
     pub VAO: u32,
 
     VBO: u32,
@@ -42,9 +46,9 @@ pub struct Mesh{
 }
 
 impl Mesh{
-    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, textures: Vec<Texture>) -> Mesh{
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, material: Material) -> Mesh{
         let mut mesh = Mesh{
-            vertices, indices, textures,
+            vertices, indices, material,
             VAO: 0, VBO: 0, EBO: 0,
         };
 
@@ -52,29 +56,12 @@ impl Mesh{
         mesh
     }
 
+    /// Draws the mesh with its material bound.
+    ///
+    /// # Safety
+    /// `shader` must be in use in the current OpenGL context, the one the mesh was created in.
     pub unsafe fn draw(&self, shader: &Shader){
-        let mut diffuseN = 0;
-        let mut specularN = 0;
-
-        for (i, texture) in self.textures.iter().enumerate() {
-            gl::ActiveTexture(gl::TEXTURE0 + i as u32);
-
-            let name = &texture.type_;
-            let number = match name.as_str() {
-                "texture_diffuse" => {
-                    diffuseN += 1;
-                    diffuseN
-                },
-                "texture_specular" => {
-                    specularN += 1;
-                    specularN
-                },
-                _ => panic!("unknown texture type")
-            };
-
-            shader.setInt(&format!("material.{}{}", name, number), i as i32);
-            gl::BindTexture(gl::TEXTURE_2D, texture.id);
-        }
+        self.material.bind(shader);
 
         gl::BindVertexArray(self.VAO);
         gl::DrawElements(gl::TRIANGLES, self.indices.len() as i32, gl::UNSIGNED_INT, ptr::null());
@@ -114,4 +101,14 @@ impl Mesh{
         gl::BindVertexArray(0);
     }
 
-}
\ No newline at end of file
+}
+
+impl Drop for Mesh{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.VAO);
+            gl::DeleteBuffers(1, &self.VBO);
+            gl::DeleteBuffers(1, &self.EBO);
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use std::os::raw::c_void;
 use std::path::Path;
 
-use cgmath::{vec2, vec3};
+use cgmath::{Point3, vec2, vec3};
 use gl;
 use image;
 use image::DynamicImage::*;
 use image::GenericImageView;
 use tobj;
 
+use crate::material::Material;
 use crate::mesh::{ Mesh, Texture, Vertex };
 use crate::shader::Shader;
 
@@ -19,10 +20,21 @@ pub struct Model {
 }
 
 impl Model {
-    pub fn new(path: &str) -> Model{
+    /// Loads an OBJ file and the textures its materials name, failing if any of them can't be read.
+    pub fn new(path: &str) -> Result<Model, String>{
         let mut model = Model::default();
-        model.loadModel(path);
-        model
+        model.loadModel(path)?;
+        Ok(model)
+    }
+
+    /// Opposite corners of the box around every vertex, in model space. `None` for a model without vertices.
+    pub fn bounds(&self) -> Option<(Point3<f32>, Point3<f32>)>{
+        let mut positions = self.meshes.iter().flat_map(|mesh| mesh.vertices.iter().map(|vertex| vertex.position));
+        let first = positions.next()?;
+        Some(positions.fold((Point3::new(first.x, first.y, first.z), Point3::new(first.x, first.y, first.z)), |(min, max), p| (
+            Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+            Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+        )))
     }
 
     pub fn draw(&self, shader: &Shader){
@@ -31,13 +43,10 @@ impl Model {
         }
     }
 
-    fn loadModel(&mut self, path: &str){
-        let path = Path::new(path);
-
-        self.directory = path.parent().unwrap_or_else(|| Path::new("")).to_str().unwrap().into();
-        let obj = tobj::load_obj(path);
+    fn loadModel(&mut self, path: &str) -> Result<(), String>{
+        let (models, materials) = tobj::load_obj(Path::new(path)).map_err(|err| format!("failed to load {}: {}", path, err))?;
+        self.directory = Path::new(path).parent().unwrap_or_else(|| Path::new("")).to_string_lossy().into();
 
-        let (models, materials) = obj.unwrap();
         for model in models{
             let mesh = &model.mesh;
             let num_vertices = mesh.positions.len()/3;
@@ -46,57 +55,51 @@ impl Model {
             let indices: Vec<u32> = mesh.indices.clone();
             
             let (p, n, t) = (&mesh.positions, &mesh.normals, &mesh.texcoords);
+            if n.len() != p.len() || t.len() != num_vertices * 2 {
+                return Err(format!("{}: mesh '{}' needs a normal and texture coordinates for every vertex", path, model.name));
+            }
             for i in 0..num_vertices{
                 vertices.push(Vertex{
                     position: vec3(p[i*3], p[i*3+1], p[i*3+2]),
                     normal: vec3(n[i*3], n[i*3+1], n[i*3+2]),
                     tex_coords: vec2(t[i*2], t[i*2+1]),
-                    ..Vertex::default()
                 })
             }
 
-            let mut textures = Vec::<Texture>::new();
-            if let Some(material_id) = mesh.material_id {
-                let material = &materials[material_id];
-
-                if !material.diffuse_texture.is_empty() {
-                    let texture = self.loadMaterialTexture(&material.diffuse_texture, "texture_diffuse");
-                    textures.push(texture);
-                }
-
-                if !material.specular_texture.is_empty() {
-                    let texture = self.loadMaterialTexture(&material.specular_texture, "texture_specular");
-                    textures.push(texture);
-                }
-            }
-            self.meshes.push(Mesh::new(vertices, indices, textures));
+            let material = match mesh.material_id.and_then(|material_id| materials.get(material_id)) {
+                Some(material) => Material::from_mtl(material, |path, srgb| self.loadMaterialTexture(path, srgb))?,
+                None => Material::default(),
+            };
+            self.meshes.push(Mesh::new(vertices, indices, material));
         }
+        Ok(())
     }
 
-    fn loadMaterialTexture(&mut self, path: &str, typeName: &str) -> Texture{
+    fn loadMaterialTexture(&mut self, path: &str, srgb: bool) -> Result<Texture, String>{
         {
-            let texture = self.textures_loaded.iter().find(|t| t.path == path);
+            let texture = self.textures_loaded.iter().find(|t| t.path == path && t.srgb == srgb);
             if let Some(texture) = texture{
-                return texture.clone();
+                return Ok(texture.clone());
             }
         }
 
-        let id = unsafe {
-            let filename = format!("{}/{}", self.directory, path);
+        let filename = format!("{}/{}", self.directory, path);
+        let img = image::open(Path::new(&filename)).map_err(|err| format!("failed to load texture {}: {}", filename, err))?;
+        let img = img.flipv();
+        let (format, internalFormat) = match img{
+            ImageLuma8(_) => (gl::RED, gl::RED),
+            ImageLumaA8(_) => (gl::RG, gl::RG),
+            ImageRgb8(_) if srgb => (gl::SRGB, gl::RGB),
+            ImageRgba8(_) if srgb => (gl::SRGB_ALPHA, gl::RGBA),
+            ImageRgb8(_) => (gl::RGB, gl::RGB),
+            ImageRgba8(_) => (gl::RGBA, gl::RGBA),
+            _ => return Err(format!("unsupported image format in {}", filename)),
+        };
 
+        let id = unsafe {
             let mut texture_id = 0;
             gl::GenTextures(1, &mut texture_id);
 
-            let img = image::open(&Path::new(&filename)).expect(&format!("Failed to load texture: {}", filename));
-            let img = img.flipv();
-            let (format, internalFormat) = match img{
-                ImageLuma8(_) => (gl::RED, gl::RED),
-                ImageLumaA8(_) => (gl::RG, gl::RG),
-                ImageRgb8(_) => (gl::SRGB, gl::RGB),
-                ImageRgba8(_) => (gl::SRGB_ALPHA, gl::RGBA),
-                _ => panic!("Unsupported image format")
-            };
-
             let data = img.raw_pixels();
 
             gl::BindTexture(gl::TEXTURE_2D, texture_id);
@@ -114,11 +117,39 @@ impl Model {
 
         let texture = Texture {
             id,
-            type_: typeName.into(),
+            srgb,
             path: path.into()
         };
 
         self.textures_loaded.push(texture.clone());
-        texture
+        Ok(texture)
+    }
+}
+
+impl Drop for Model{
+    fn drop(&mut self){
+        for texture in &self.textures_loaded{
+            unsafe { gl::DeleteTextures(1, &texture.id); }
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn bad_files_are_errors(){
+        let err = Model::new("models/missing.obj").err().unwrap();
+        assert!(err.starts_with("failed to load models/missing.obj"), "{}", err);
+
+        // Positions only, so there are no normals or texture coordinates to build vertices from
+        let path = env::temp_dir().join("ropengl_positions_only.obj");
+        fs::write(&path, "o triangle\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let err = Model::new(path.to_str().unwrap()).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(err.ends_with("mesh 'triangle' needs a normal and texture coordinates for every vertex"), "{}", err);
+    }
+}
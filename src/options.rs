@@ -0,0 +1,126 @@
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use ropengl::renderer::{AntiAliasing, Pipeline, RenderTarget};
+
+const USAGE: &str = "usage: ropengl [--headless] [--frames N] [--capture-frame N] [--capture-target final|scene|shadow] [--output PATH]
+               [--scene PATH] [--time SECONDS] [--camera X,Y,Z,YAW,PITCH] [--cluster-heatmap]
+               [--pipeline forward|deferred] [--aa none|msaa2|msaa4|msaa8|fxaa|taa]";
+
+/// Camera position and orientation in degrees.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub pos: (f32, f32, f32),
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl FromStr for Pose {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s.split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|err| format!("invalid camera pose {}: {}", s, err))?;
+
+        match values.as_slice() {
+            [x, y, z, yaw, pitch] => Ok(Pose { pos: (*x, *y, *z), yaw: *yaw, pitch: *pitch }),
+            _ => Err(format!("camera pose needs 5 values, got {}", values.len())),
+        }
+    }
+}
+
+/// Command line options for the renderer.
+pub struct Options {
+    /// Render into the offscreen framebuffer without a window. The context comes from EGL's surfaceless
+    /// platform rather than GLFW, so no display or X server is needed, and Mesa's llvmpipe
+    /// (`LIBGL_ALWAYS_SOFTWARE=1`) renders without a GPU.
+    pub headless: bool,
+    /// Number of frames rendered before exiting in headless mode.
+    pub frames: u32,
+    /// Frame to save in headless mode, counting from 0.
+    pub capture_frame: Option<u32>,
+    /// Target saved by `capture_frame` or the F12 key.
+    pub capture_target: RenderTarget,
+    /// Where headless captures are written. The extension is replaced to match the target's format.
+    pub output: String,
+    /// Scene file to load.
+    pub scene: String,
+    /// Time in seconds at the first headless frame.
+    pub time: f32,
+    /// Starting camera pose, overriding the one in the scene file.
+    pub camera: Option<Pose>,
+    /// Start with the light cluster heat-map shown, toggled with the H key.
+    pub cluster_heatmap: bool,
+    /// Forward or deferred shading, fixed at start-up.
+    pub pipeline: Pipeline,
+    /// Anti-aliasing mode, fixed at start-up.
+    pub anti_aliasing: AntiAliasing,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            headless: false,
+            frames: 1,
+            capture_frame: None,
+            capture_target: RenderTarget::Final,
+            output: "capture.png".into(),
+            scene: "scenes/default.ron".into(),
+            time: 0.0,
+            camera: None,
+            cluster_heatmap: false,
+            pipeline: Pipeline::Forward,
+            anti_aliasing: AntiAliasing::Msaa(4),
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Result<Options, String> {
+        Options::parse(env::args().skip(1))
+    }
+
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                "--frames" => options.frames = parse_value(&arg, args.next())?,
+                "--capture-frame" => options.capture_frame = Some(parse_value(&arg, args.next())?),
+                "--capture-target" => options.capture_target = parse_value(&arg, args.next())?,
+                "--output" => options.output = parse_value(&arg, args.next())?,
+                "--scene" => options.scene = parse_value(&arg, args.next())?,
+                "--time" => options.time = parse_value(&arg, args.next())?,
+                "--camera" => options.camera = Some(parse_value(&arg, args.next())?),
+                "--cluster-heatmap" => options.cluster_heatmap = true,
+                "--pipeline" => options.pipeline = parse_value(&arg, args.next())?,
+                "--aa" => options.anti_aliasing = parse_value(&arg, args.next())?,
+                "--help" | "-h" => return Err(USAGE.into()),
+                _ => return Err(format!("unknown argument: {}\n{}", arg, USAGE)),
+            }
+        }
+
+        if options.frames == 0 {
+            return Err("--frames must be at least 1".into());
+        }
+
+        if let Some(frame) = options.capture_frame {
+            if options.headless && frame >= options.frames {
+                return Err(format!("--capture-frame {} is never rendered with --frames {}", frame, options.frames));
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_value<T>(name: &str, value: Option<String>) -> Result<T, String>
+    where T: FromStr, T::Err: Display
+{
+    let value = value.ok_or_else(|| format!("missing value for {}\n{}", name, USAGE))?;
+    value.parse().map_err(|err| format!("invalid value for {} {}: {}", name, value, err))
+}
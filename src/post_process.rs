@@ -0,0 +1,222 @@
+use gl;
+
+use crate::framebuffer::Framebuffer;
+use crate::shader::Shader;
+
+/// 3x3 laplacian edge detection.
+pub const EDGE_DETECT: [f32; 9] = [
+    1.0, 1.0, 1.0,
+    1.0, -8.0, 1.0,
+    1.0, 1.0, 1.0,
+];
+pub const SHARPEN: [f32; 9] = [
+    -1.0, -1.0, -1.0,
+    -1.0, 9.0, -1.0,
+    -1.0, -1.0, -1.0,
+];
+/// 3x3 Gaussian blur.
+pub const BLUR: [f32; 9] = [
+    1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0,
+    2.0 / 16.0, 4.0 / 16.0, 2.0 / 16.0,
+    1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0,
+];
+
+/// A uniform value set on an effect's shader before it runs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parameter{
+    Int(i32),
+    Float(f32),
+    Vec2(f32, f32),
+    Vec3(f32, f32, f32),
+    /// Sets `name[0]`, `name[1]`...
+    FloatArray(Vec<f32>),
+}
+
+/// A texture sampled by an effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input{
+    /// Output of the previous enabled pass, or the tone mapped scene for the first.
+    Previous,
+    /// Tone mapped scene before any effect.
+    Original,
+    Texture(u32),
+}
+
+/// One fullscreen pass of a `PostProcessChain`.
+pub struct EffectPass{
+    /// Name used to find the pass, e.g. to toggle it at runtime.
+    pub name: String,
+    pub enabled: bool,
+    pub parameters: Vec<(String, Parameter)>,
+    /// Sampler uniforms and the textures bound to them, on units 0, 1, 2...
+    pub inputs: Vec<(String, Input)>,
+    shader: Shader,
+}
+
+impl EffectPass{
+    /// A pass running `fragment_path` over a fullscreen quad, with the previous pass bound to the `image` sampler.
+    /// Every pass also gets `texel_size`, the size of one pixel in texture coordinates, and `time` in seconds.
+    pub fn new(name: &str, fragment_path: &str) -> EffectPass{
+        EffectPass{
+            name: name.into(),
+            enabled: true,
+            parameters: vec!(),
+            inputs: vec!(("image".into(), Input::Previous)),
+            shader: Shader::new("shaders/postpro.vert", fragment_path),
+        }
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: Parameter) -> EffectPass{
+        self.set_parameter(name, value);
+        self
+    }
+
+    pub fn with_input(mut self, name: &str, input: Input) -> EffectPass{
+        self.inputs.push((name.into(), input));
+        self
+    }
+
+    /// Replaces the value of a parameter, adding it if the pass doesn't have it yet.
+    pub fn set_parameter(&mut self, name: &str, value: Parameter){
+        match self.parameters.iter_mut().find(|(n, _)| n == name){
+            Some((_, v)) => *v = value,
+            None => self.parameters.push((name.into(), value)),
+        }
+    }
+
+    /// Convolves the image with a row major 3x3 kernel, such as `EDGE_DETECT`.
+    pub fn kernel(name: &str, kernel: [f32; 9]) -> EffectPass{
+        EffectPass::new(name, "shaders/effects/kernel.frag")
+            .with_parameter("kernel", Parameter::FloatArray(kernel.to_vec()))
+    }
+
+    pub fn grayscale() -> EffectPass{
+        EffectPass::new("grayscale", "shaders/effects/grayscale.frag")
+    }
+
+    pub fn invert() -> EffectPass{
+        EffectPass::new("invert", "shaders/effects/invert.frag")
+    }
+
+    /// Darkens the corners, starting at `radius` from the centre, in units of half the screen diagonal,
+    /// and fading over `softness`.
+    pub fn vignette(radius: f32, softness: f32) -> EffectPass{
+        EffectPass::new("vignette", "shaders/effects/vignette.frag")
+            .with_parameter("radius", Parameter::Float(radius))
+            .with_parameter("softness", Parameter::Float(softness))
+    }
+
+    /// Splits the red and blue channels apart towards the edges of the screen, by up to `strength` pixels.
+    pub fn chromatic_aberration(strength: f32) -> EffectPass{
+        EffectPass::new("chromatic_aberration", "shaders/effects/chromatic_aberration.frag")
+            .with_parameter("strength", Parameter::Float(strength))
+    }
+
+    /// Adds animated noise of up to `strength`.
+    pub fn film_grain(strength: f32) -> EffectPass{
+        EffectPass::new("film_grain", "shaders/effects/film_grain.frag")
+            .with_parameter("strength", Parameter::Float(strength))
+    }
+
+    /// Smooths jagged edges, see `AntiAliasing::Fxaa`.
+    pub fn fxaa() -> EffectPass{
+        EffectPass::new("fxaa", "shaders/effects/fxaa.frag")
+    }
+
+    unsafe fn set_uniforms(&self, previous: u32, original: u32, width: i32, height: i32, time: f32){
+        let shader = &self.shader;
+        shader.useProgram();
+        shader.setUniform2f("texel_size", (1.0 / width as f32, 1.0 / height as f32));
+        shader.setFloat("time", time);
+
+        for (name, value) in self.parameters.iter(){
+            match value{
+                Parameter::Int(v) => shader.setInt(name, *v),
+                Parameter::Float(v) => shader.setFloat(name, *v),
+                Parameter::Vec2(x, y) => shader.setUniform2f(name, (*x, *y)),
+                Parameter::Vec3(x, y, z) => shader.setUniform3f(name, (*x, *y, *z)),
+                Parameter::FloatArray(values) => {
+                    for (i, v) in values.iter().enumerate(){
+                        shader.setFloat(&format!("{}[{}]", name, i), *v);
+                    }
+                },
+            }
+        }
+
+        for (unit, (name, input)) in self.inputs.iter().enumerate(){
+            let texture = match input{
+                Input::Previous => previous,
+                Input::Original => original,
+                Input::Texture(texture) => *texture,
+            };
+            shader.setInt(name, unit as i32);
+            gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+}
+
+/// Effects applied in order to the tone mapped image. Passes can be added, removed, reordered and toggled
+/// between frames.
+#[derive(Default)]
+pub struct PostProcessChain{
+    pub passes: Vec<EffectPass>,
+}
+
+impl PostProcessChain{
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut EffectPass>{
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    /// Flips whether the pass at `index` runs. Returns the new state, or `None` if there is no such pass.
+    pub fn toggle(&mut self, index: usize) -> Option<bool>{
+        self.passes.get_mut(index).map(|pass| {
+            pass.enabled = !pass.enabled;
+            pass.enabled
+        })
+    }
+
+    /// Moves the pass at `from` to `to`, shifting the passes in between. Returns false, leaving the chain as it was,
+    /// when either index is out of range.
+    pub fn move_pass(&mut self, from: usize, to: usize) -> bool{
+        if from >= self.passes.len() || to >= self.passes.len() {
+            return false;
+        }
+        let pass = self.passes.remove(from);
+        self.passes.insert(to, pass);
+        true
+    }
+
+    /// Whether any pass is enabled.
+    pub fn is_active(&self) -> bool{
+        self.passes.iter().any(|pass| pass.enabled)
+    }
+
+    /// Runs the enabled passes over `input`, ping-ponging between `targets` and drawing the last one into `output`.
+    /// `input` stays readable as `Input::Original`.
+    ///
+    /// # Safety
+    /// The OpenGL context the passes were created in must be current. `input` must be a texture in it that doesn't
+    /// belong to `targets`, and `quad_vao` must hold a fullscreen quad laid out for `shaders/postpro.vert`.
+    pub unsafe fn apply(&self, input: u32, targets: &[Framebuffer; 2], output: &Framebuffer, quad_vao: u32, time: f32){
+        let passes: Vec<&EffectPass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+        gl::BindVertexArray(quad_vao);
+
+        let mut previous = input;
+        for (i, pass) in passes.iter().enumerate(){
+            let target = if i + 1 == passes.len() { output } else { &targets[i % 2] };
+            target.bind();
+            pass.set_uniforms(previous, input, target.width, target.height, time);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            previous = target.texture;
+        }
+
+        gl::BindVertexArray(0);
+        gl::Enable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+}
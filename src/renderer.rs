@@ -0,0 +1,1200 @@
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+use std::str::FromStr;
+
+use cgmath::{Matrix4, vec3, perspective, Quaternion, Vector3, Vector4};
+use cgmath::prelude::*;
+use gl;
+use gl::types::*;
+
+use crate::bloom::Bloom;
+use crate::camera::Camera;
+use crate::capture::Capture;
+use crate::cluster::{Clusters, CLUSTER_GRID};
+use crate::exposure::AutoExposure;
+use crate::framebuffer::Framebuffer;
+use crate::ibl::{BrdfLut, PREFILTER_LEVELS};
+use crate::model::Model;
+use crate::light::{limit_lights, Light, LightBlock, PointLight};
+use crate::post_process::{EffectPass, PostProcessChain};
+use crate::scene::{Scene, ShadowSettings};
+use crate::shadow::{cube_face_transforms, CascadedShadowMap, PointShadowMaps, SpotShadowAtlas, MAX_SHADOWED_POINT_LIGHTS, POINT_SHADOW_FAR, SPOT_SHADOW_NEAR};
+use crate::shader::Shader;
+use crate::ssao::Ssao;
+use crate::taa::TemporalAa;
+
+/// Render targets that can be read back with `Renderer::capture`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RenderTarget {
+    /// Post-processed output shown in the window.
+    Final,
+    /// Resolved HDR scene colour before tone mapping and post-processing, saved as EXR.
+    Scene,
+    /// Directional light shadow cascade depths, stacked from the nearest cascade at the top.
+    Shadow,
+}
+
+impl FromStr for RenderTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "final" => Ok(RenderTarget::Final),
+            "scene" => Ok(RenderTarget::Scene),
+            "shadow" => Ok(RenderTarget::Shadow),
+            _ => Err(format!("unknown render target: {}", s)),
+        }
+    }
+}
+
+/// How lit geometry is shaded.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Pipeline {
+    /// Models are shaded as they are drawn, with point lights culled per cluster.
+    Forward,
+    /// Models write a G-buffer, then each light shades the pixels inside its volume. Multisampling is not
+    /// supported for the deferred pass, so the scene is rendered single sampled.
+    Deferred,
+}
+
+impl FromStr for Pipeline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forward" => Ok(Pipeline::Forward),
+            "deferred" => Ok(Pipeline::Deferred),
+            _ => Err(format!("unknown pipeline: {}", s)),
+        }
+    }
+}
+
+/// How edges of the scene are smoothed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AntiAliasing {
+    /// Multisampling with 1, 2, 4 or 8 samples per pixel, lowered at start-up to what the GPU supports.
+    /// Only the forward pipeline is multisampled.
+    Msaa(i32),
+    /// Fast approximate anti-aliasing, a post pass over the tone mapped image.
+    Fxaa,
+    /// Temporal anti-aliasing: the projection is jittered by a fraction of a pixel every frame and the frames
+    /// are blended together, reprojected with the camera's movement.
+    Taa,
+}
+
+impl FromStr for AntiAliasing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" | "msaa1" => Ok(AntiAliasing::Msaa(1)),
+            "msaa2" => Ok(AntiAliasing::Msaa(2)),
+            "msaa4" => Ok(AntiAliasing::Msaa(4)),
+            "msaa8" => Ok(AntiAliasing::Msaa(8)),
+            "fxaa" => Ok(AntiAliasing::Fxaa),
+            "taa" => Ok(AntiAliasing::Taa),
+            _ => Err(format!("unknown anti-aliasing mode: {}", s)),
+        }
+    }
+}
+
+impl AntiAliasing {
+    /// Samples per pixel of the main scene target.
+    pub fn samples(&self) -> i32{
+        match self{
+            AntiAliasing::Msaa(samples) => *samples,
+            AntiAliasing::Fxaa | AntiAliasing::Taa => 1,
+        }
+    }
+}
+
+/// The largest of 1, 2, 4 and 8 samples that is at most `requested` and `max_samples`.
+pub fn supported_samples(requested: i32, max_samples: i32) -> i32{
+    [8, 4, 2, 1].iter().copied().find(|&samples| samples <= requested && samples <= max_samples).unwrap_or(1)
+}
+
+pub struct RendererConfig{
+    pub width: i32,
+    pub height: i32,
+    pub anti_aliasing: AntiAliasing,
+    /// Width and height of each cascade of the directional light shadow map.
+    pub shadow_size: i32,
+    /// Number of slices the view frustum is split into for directional shadows, at most `MAX_CASCADES`.
+    pub shadow_cascades: usize,
+    /// Width and height of each cube face of the point light shadow maps.
+    pub point_shadow_size: i32,
+    /// Width and height of the atlas all spot light shadow maps share.
+    pub spot_shadow_atlas_size: i32,
+    /// Width and height of each spot light's tile, halved when the atlas runs out of room.
+    pub spot_shadow_size: i32,
+    pub pipeline: Pipeline,
+}
+
+impl Default for RendererConfig{
+    fn default() -> Self{
+        RendererConfig{
+            width: 800,
+            height: 600,
+            anti_aliasing: AntiAliasing::Msaa(4),
+            shadow_size: 2048,
+            shadow_cascades: 4,
+            point_shadow_size: 512,
+            spot_shadow_atlas_size: 4096,
+            spot_shadow_size: 1024,
+            pipeline: Pipeline::Forward,
+        }
+    }
+}
+
+/// Light volume meshes are scaled up by this much so their flat faces still enclose the curved volume.
+const VOLUME_SCALE: f32 = 1.05;
+
+/// Colour of the lamp cubes, bright enough to bloom.
+const LAMP_BRIGHTNESS: f32 = 4.0;
+
+/// Owns every GL object used to draw a `Scene` and releases them on drop.
+pub struct Renderer{
+    config: RendererConfig,
+
+    post_shader: Shader,
+    lit_shader: Shader,
+    lamp_shader: Shader,
+    outline_shader: Shader,
+    transparent_shader: Shader,
+    skybox_shader: Shader,
+    point_shader: Shader,
+    instance_shader: Shader,
+    shadow_shader: Shader,
+    instanced_shadow_shader: Shader,
+    point_shadow_shader: Shader,
+    instanced_point_shadow_shader: Shader,
+    gbuffer_shader: Shader,
+    deferred_quad_shader: Shader,
+    deferred_volume_shader: Shader,
+
+    /// Main scene target in HDR, multisampled for the forward pipeline.
+    ms_target: Framebuffer,
+    /// Position, normal, albedo/AO, metallic/roughness and emissive textures, only for the deferred pipeline.
+    gbuffer: Option<Framebuffer>,
+    scene_target: Framebuffer,
+    /// Tone mapped scene, the input of FXAA or the scene's post-processing effects.
+    ldr_target: Framebuffer,
+    /// FXAA output when the scene's effects run after it.
+    fxaa_target: Framebuffer,
+    /// Ping-pong targets for the post-processing effects.
+    effect_targets: [Framebuffer; 2],
+    output_target: Framebuffer,
+    shadow_map: CascadedShadowMap,
+    point_shadows: PointShadowMaps,
+    spot_shadows: SpotShadowAtlas,
+
+    quad_vao: u32,
+    quad_vbo: u32,
+    skybox_vao: u32,
+    skybox_vbo: u32,
+    grid_vao: u32,
+    grid_vbo: u32,
+    grid_instance_vbo: u32,
+    grid_instances: i32,
+    sphere_vao: u32,
+    sphere_vbo: u32,
+    sphere_vertices: i32,
+    cone_vao: u32,
+    cone_vbo: u32,
+    cone_vertices: i32,
+
+    ubo: u32,
+    /// Scene lights, bound to uniform block binding 1.
+    light_ubo: u32,
+    light_block: Box<LightBlock>,
+    clusters: Clusters,
+    brdf_lut: BrdfLut,
+    auto_exposure: AutoExposure,
+    bloom: Bloom,
+    ssao: Ssao,
+    /// Only for `AntiAliasing::Taa`.
+    taa: Option<TemporalAa>,
+    /// The FXAA pass for `AntiAliasing::Fxaa`, otherwise empty.
+    fxaa: PostProcessChain,
+    /// Colour lit surfaces by how many point lights their cluster holds instead of shading them.
+    cluster_heatmap: bool,
+    /// Set once lights beyond the supported maximum have been reported, so it isn't repeated every frame.
+    reported_dropped_lights: bool,
+
+    lamp: Model,
+}
+
+impl Renderer{
+    /// Fails when the scene target can't be created with the configured anti-aliasing, which would otherwise
+    /// render nothing but black.
+    pub fn new(mut config: RendererConfig) -> Result<Renderer, String>{
+        if let AntiAliasing::Msaa(samples) = config.anti_aliasing {
+            // Multisampled renderbuffers, colour textures and depth textures each have their own limit
+            let max_samples = [gl::MAX_SAMPLES, gl::MAX_COLOR_TEXTURE_SAMPLES, gl::MAX_DEPTH_TEXTURE_SAMPLES].iter().map(|&limit| {
+                let mut max = 0;
+                unsafe { gl::GetIntegerv(limit, &mut max); }
+                max
+            }).min().unwrap_or(0);
+            let supported = supported_samples(samples, max_samples);
+            if supported != samples {
+                println!("ERROR::RENDERER:: {}x MSAA is not supported, using {}x", samples, supported);
+                config.anti_aliasing = AntiAliasing::Msaa(supported);
+            }
+        }
+
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::LEQUAL);
+            gl::Enable(gl::MULTISAMPLE);
+            gl::Enable(gl::STENCIL_TEST);
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+            // Filter across cube map face edges, which the rough levels of prefiltered environments rely on
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            //gl::Enable(gl::CULL_FACE);
+            gl::Enable(gl::PROGRAM_POINT_SIZE);
+            gl::Enable(gl::BLEND);
+            gl::CullFace(gl::FRONT);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+
+        let (quad_vao, quad_vbo) = unsafe { create_quad() };
+        let (skybox_vao, skybox_vbo) = unsafe { create_skybox_cube() };
+        let (grid_vao, grid_vbo, grid_instance_vbo, grid_instances) = unsafe { create_cube_grid() };
+        let (sphere_vao, sphere_vbo, sphere_vertices) = unsafe { create_positions(&sphere_vertices()) };
+        let (cone_vao, cone_vbo, cone_vertices) = unsafe { create_positions(&cone_vertices()) };
+
+        //let lit_shader = Shader::newGeometry("shaders/shader.vert","shaders/shader.frag", "shaders/explode.geom");
+        let lit_shader = Shader::new("shaders/shader.vert","shaders/shader.frag");
+        lit_shader.bindUniformBlock("Matrices", 0);
+        lit_shader.bindUniformBlock("Lights", 1);
+
+        let deferred_quad_shader = Shader::new("shaders/postpro.vert", "shaders/deferred.frag");
+        deferred_quad_shader.bindUniformBlock("Lights", 1);
+        let deferred_volume_shader = Shader::new("shaders/lamp.vert", "shaders/deferred.frag");
+        deferred_volume_shader.bindUniformBlock("Matrices", 0);
+        deferred_volume_shader.bindUniformBlock("Lights", 1);
+
+        let point_shadow_shader = Shader::newGeometry("shaders/point_shadow.vert", "shaders/point_shadow.frag", "shaders/point_shadow.geom");
+        let instanced_point_shadow_shader = Shader::newGeometry("shaders/point_shadow_instanced.vert", "shaders/point_shadow.frag", "shaders/point_shadow.geom");
+        for shader in [&point_shadow_shader, &instanced_point_shadow_shader].iter(){
+            shader.useProgram();
+            for (i, &transform) in cube_face_transforms().iter().enumerate(){
+                shader.setMat4(&format!("face_transforms[{}]", i), transform);
+            }
+            shader.setFloat("far", POINT_SHADOW_FAR);
+        }
+
+        let ubo = unsafe {
+            let mut ubo = 0;
+            gl::GenBuffers(1, &mut ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, ubo);
+            gl::BufferData(gl::UNIFORM_BUFFER, 2 * mem::size_of::<Matrix4<f32>>() as isize, ptr::null(), gl::STATIC_DRAW);
+            gl::BindBufferRange(gl::UNIFORM_BUFFER, 0, ubo, 0, 2 * mem::size_of::<Matrix4<f32>>() as isize);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            ubo
+        };
+
+        let light_ubo = unsafe {
+            let mut ubo = 0;
+            gl::GenBuffers(1, &mut ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, ubo);
+            gl::BufferData(gl::UNIFORM_BUFFER, mem::size_of::<LightBlock>() as isize, ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, 1, ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            ubo
+        };
+
+        let renderer = Renderer{
+            post_shader: Shader::new("shaders/postpro.vert", "shaders/postpro.frag"),
+            lit_shader,
+            lamp_shader: Shader::new("shaders/lamp.vert","shaders/lamp.frag"),
+            outline_shader: Shader::new("shaders/shader.vert","shaders/outlineShader.frag"),
+            transparent_shader: Shader::new("shaders/shader.vert","shaders/transparentShader.frag"),
+            skybox_shader: Shader::new("shaders/skybox.vert", "shaders/skybox.frag"),
+            point_shader: Shader::newGeometry("shaders/point.vert", "shaders/lamp.frag", "shaders/point.geom"),
+            instance_shader: Shader::new("shaders/instance.vert", "shaders/lamp.frag"),
+            shadow_shader: Shader::new("shaders/shadow.vert", "shaders/shadow.frag"),
+            instanced_shadow_shader: Shader::new("shaders/shadow_instanced.vert", "shaders/shadow.frag"),
+            point_shadow_shader,
+            instanced_point_shadow_shader,
+            gbuffer_shader: Shader::new("shaders/shader.vert", "shaders/gbuffer.frag"),
+            deferred_quad_shader,
+            deferred_volume_shader,
+
+            ms_target: main_target(&config, config.width, config.height),
+            gbuffer: gbuffer(&config, config.width, config.height),
+            scene_target: Framebuffer::new(config.width, config.height, gl::RGBA16F),
+            ldr_target: Framebuffer::new(config.width, config.height, gl::RGBA16F),
+            fxaa_target: Framebuffer::new(config.width, config.height, gl::RGBA16F),
+            effect_targets: effect_targets(config.width, config.height),
+            output_target: Framebuffer::new(config.width, config.height, gl::SRGB8),
+            shadow_map: CascadedShadowMap::new(config.shadow_size, config.shadow_cascades),
+            point_shadows: PointShadowMaps::new(config.point_shadow_size),
+            spot_shadows: SpotShadowAtlas::new(config.spot_shadow_atlas_size, config.spot_shadow_size),
+
+            quad_vao, quad_vbo,
+            skybox_vao, skybox_vbo,
+            grid_vao, grid_vbo, grid_instance_vbo, grid_instances,
+            sphere_vao, sphere_vbo, sphere_vertices,
+            cone_vao, cone_vbo, cone_vertices,
+
+            ubo,
+            light_ubo,
+            light_block: LightBlock::new(),
+            clusters: Clusters::new(),
+            brdf_lut: BrdfLut::new(),
+            auto_exposure: AutoExposure::new(),
+            bloom: Bloom::new(config.width, config.height),
+            ssao: Ssao::new(config.width, config.height, config.pipeline == Pipeline::Forward),
+            taa: if config.anti_aliasing == AntiAliasing::Taa { Some(TemporalAa::new(config.width, config.height)) } else { None },
+            fxaa: PostProcessChain{ passes: if config.anti_aliasing == AntiAliasing::Fxaa { vec!(EffectPass::fxaa()) } else { vec!() } },
+            cluster_heatmap: false,
+            reported_dropped_lights: false,
+
+            lamp: Model::new("models/cube.obj").unwrap_or_else(|err| {
+                println!("ERROR::MODEL:: {}", err);
+                Model::default()
+            }),
+
+            config,
+        };
+
+        if !renderer.ms_target.is_complete() {
+            return Err(format!("the scene target with {} samples per pixel is not complete", renderer.config.anti_aliasing.samples()));
+        }
+        Ok(renderer)
+    }
+
+    pub fn width(&self) -> i32{
+        self.config.width
+    }
+
+    pub fn height(&self) -> i32{
+        self.config.height
+    }
+
+    /// Recreates the screen sized render targets for a new window size.
+    pub fn resize(&mut self, width: i32, height: i32){
+        if width <= 0 || height <= 0 || (width == self.config.width && height == self.config.height) {
+            return;
+        }
+
+        self.config.width = width;
+        self.config.height = height;
+        self.ms_target = main_target(&self.config, width, height);
+        self.gbuffer = gbuffer(&self.config, width, height);
+        self.scene_target = Framebuffer::new(width, height, gl::RGBA16F);
+        self.ldr_target = Framebuffer::new(width, height, gl::RGBA16F);
+        self.fxaa_target = Framebuffer::new(width, height, gl::RGBA16F);
+        self.effect_targets = effect_targets(width, height);
+        self.bloom.resize(width, height);
+        self.ssao.resize(width, height);
+        if let Some(taa) = &mut self.taa {
+            taa.resize(width, height);
+        }
+        self.output_target = Framebuffer::new(width, height, gl::SRGB8);
+    }
+
+    pub fn cluster_heatmap(&self) -> bool{
+        self.cluster_heatmap
+    }
+
+    /// Only the forward pipeline draws the heat-map.
+    pub fn set_cluster_heatmap(&mut self, enabled: bool){
+        self.cluster_heatmap = enabled;
+    }
+
+    /// Draws `scene` from `camera` into the output target. Call `present` to show it in the window.
+    pub fn render(&mut self, scene: &Scene, camera: &Camera){
+        let model_mat: Matrix4<f32> = Matrix4::identity();
+        let view: Matrix4<f32> = camera.get_view();
+        let proj: Matrix4<f32> = camera.get_projection();
+        let sky_proj = perspective(camera.fov, camera.aspect, camera.near, camera.far);
+        // Geometry is drawn jittered for TAA, while light culling and reprojection use the exact projection
+        let (jittered_proj, sky_proj) = match &self.taa{
+            Some(taa) => (taa.jittered(proj, self.config.width, self.config.height), taa.jittered(sky_proj, self.config.width, self.config.height)),
+            None => (proj, sky_proj),
+        };
+        // Reversed-Z gives nearer surfaces greater depths, so everything drawn from the camera flips the depth test
+        let (far_depth, depth_func) = if camera.reversed_z() { (0.0, gl::GEQUAL) } else { (1.0, gl::LEQUAL) };
+        let mut lights = scene.lights();
+        let dropped = limit_lights(&mut lights);
+        if dropped > 0 && !self.reported_dropped_lights {
+            println!("ERROR::RENDERER:: {} lights beyond the supported maximum are ignored", dropped);
+            self.reported_dropped_lights = true;
+        }
+        let lamp_positions = scene.point_light_positions();
+
+        unsafe {
+            self.render_shadows(scene, camera, &lights, &lamp_positions);
+
+            gl::ClearDepth(far_depth);
+            gl::DepthFunc(depth_func);
+            self.ms_target.bind();
+            gl::ClearColor(0.0, 0.5, 0.5, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            gl::Enable(gl::DEPTH_TEST);
+
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
+            gl::BufferSubData(gl::UNIFORM_BUFFER, 0, mem::size_of::<Matrix4<f32>>() as isize, jittered_proj.as_ptr() as *const c_void);
+            gl::BufferSubData(gl::UNIFORM_BUFFER, mem::size_of::<Matrix4<f32>>() as isize, mem::size_of::<Matrix4<f32>>() as isize, view.as_ptr() as *const c_void);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+
+            // Every light is uploaded each frame, so lights can be added, removed or edited between frames
+            self.light_block.pack(lights.iter(), camera, &self.spot_shadows.shadows);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.light_ubo);
+            gl::BufferSubData(gl::UNIFORM_BUFFER, 0, mem::size_of::<LightBlock>() as isize, &*self.light_block as *const LightBlock as *const c_void);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+
+            let point_lights: Vec<PointLight> = lights.iter().filter_map(|light| match light{
+                Light::Point(light) => Some(*light),
+                _ => None,
+            }).collect();
+            self.clusters.update(&point_lights, &view, &proj, camera.near, camera.far);
+            self.clusters.bind(6);
+
+            gl::ActiveTexture(gl::TEXTURE5);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.shadow_map.texture);
+            gl::ActiveTexture(gl::TEXTURE13);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.point_shadows.texture);
+            gl::ActiveTexture(gl::TEXTURE14);
+            gl::BindTexture(gl::TEXTURE_2D, self.spot_shadows.texture);
+            // The cascades again, read with hardware depth comparison
+            gl::ActiveTexture(gl::TEXTURE15);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.shadow_map.texture);
+            gl::BindSampler(15, self.shadow_map.compare_sampler);
+            if let Some(environment) = &scene.environment {
+                gl::ActiveTexture(gl::TEXTURE9);
+                gl::BindTexture(gl::TEXTURE_CUBE_MAP, environment.irradiance.id);
+                gl::ActiveTexture(gl::TEXTURE10);
+                gl::BindTexture(gl::TEXTURE_CUBE_MAP, environment.prefiltered.id);
+            }
+            gl::ActiveTexture(gl::TEXTURE11);
+            gl::BindTexture(gl::TEXTURE_2D, self.brdf_lut.texture);
+            gl::ActiveTexture(gl::TEXTURE0);
+
+            match self.config.pipeline{
+                Pipeline::Forward => self.draw_forward(scene, camera),
+                Pipeline::Deferred => self.draw_deferred(scene, camera, &lights),
+            }
+
+            if scene.cube_grid {
+                self.point_shader.useProgram();
+                self.point_shader.setFloat("brightness", 1.0);
+                self.point_shader.setMat4("u_model", model_mat);
+                gl::BindVertexArray(self.grid_vao);
+                gl::DrawArrays(gl::POINTS, 0, 36);
+
+                self.instance_shader.useProgram();
+                self.instance_shader.setFloat("brightness", 1.0);
+                gl::DrawArraysInstanced(gl::TRIANGLES, 0, 36, self.grid_instances);
+
+                gl::BindVertexArray(0);
+            }
+
+            self.ms_target.bind();
+
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+            gl::StencilFunc(gl::ALWAYS, 1, 0xFF);
+            gl::StencilMask(0xFF);
+
+            self.lamp_shader.useProgram();
+            self.lamp_shader.setFloat("brightness", LAMP_BRIGHTNESS);
+
+            for &position in lamp_positions.iter(){
+                let model = Matrix4::<f32>::from_translation(position)*Matrix4::<f32>::from_scale(0.2);
+                self.lamp_shader.setMat4("u_model", model);
+
+                self.lamp.draw(&self.lamp_shader);
+            }
+
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            gl::StencilFunc(gl::ALWAYS, 1, 0xFF);
+            gl::StencilMask(0xFF);
+            self.transparent_shader.useProgram();
+
+            let mut windows = scene.window_positions.clone();
+            windows.sort_by(|a, b| {
+                let pos = camera.pos.to_vec();
+                (pos.distance2(*a)).partial_cmp(&pos.distance2(*b)).unwrap().reverse()
+            });
+
+            gl::BindVertexArray(self.quad_vao);
+            for position in windows.iter(){
+                let model_mat = Matrix4::<f32>::from_translation(*position);
+                self.transparent_shader.setMat4("u_model", model_mat);
+                gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            }
+            gl::BindVertexArray(0);
+
+            if let Some(skybox) = &scene.skybox {
+                self.skybox_shader.useProgram();
+                self.skybox_shader.setMat4("sky_projection", sky_proj);
+                self.skybox_shader.setFloat("sky_depth", far_depth as f32);
+
+                gl::BindVertexArray(self.skybox_vao);
+                gl::BindTexture(gl::TEXTURE_CUBE_MAP, skybox.id);
+                self.skybox_shader.setInt("skybox", 0);
+                gl::DrawArrays(gl::TRIANGLES, 0, 36);
+                gl::BindVertexArray(0);
+            }
+
+            gl::StencilFunc(gl::NOTEQUAL, 1, 0xFF);
+            gl::StencilMask(0x00);
+            gl::Disable(gl::DEPTH_TEST);
+
+            self.outline_shader.useProgram();
+
+            for &position in lamp_positions.iter(){
+                let model = Matrix4::<f32>::from_translation(position)*Matrix4::<f32>::from_scale(0.25);
+                self.outline_shader.setMat4("u_model", model);
+
+                self.lamp.draw(&self.outline_shader);
+            }
+
+            gl::Enable(gl::DEPTH_TEST);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            gl::StencilFunc(gl::ALWAYS, 1, 0xFF);
+            gl::StencilMask(0xFF);
+            gl::ClearDepth(1.0);
+            gl::DepthFunc(gl::LEQUAL);
+
+            self.ms_target.blit(self.scene_target.id, self.scene_target.width, self.scene_target.height);
+
+            let hdr_texture = match &mut self.taa{
+                Some(taa) => {
+                    taa.resolve(self.scene_target.texture, self.ms_target.depth_texture, proj * view, jittered_proj * view, self.quad_vao);
+                    taa.texture()
+                },
+                None => self.scene_target.texture,
+            };
+
+            if scene.post_process.auto_exposure {
+                self.auto_exposure.update(hdr_texture, self.quad_vao, scene.time);
+            }
+            let bloom = &scene.post_process.bloom;
+            if bloom.enabled {
+                self.bloom.render(hdr_texture, self.scene_target.width, self.scene_target.height, self.quad_vao, bloom);
+            }
+
+            // Without FXAA or effects, tone mapping writes the output directly
+            let fxaa = self.fxaa.is_active();
+            let effects = scene.effects.is_active();
+            let tone_mapped = if fxaa || effects { &self.ldr_target } else { &self.output_target };
+            tone_mapped.bind();
+            gl::ClearColor(0.0, 0.5, 0.5, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            gl::Disable(gl::DEPTH_TEST);
+
+            self.post_shader.useProgram();
+            self.post_shader.setInt("tone_mapping", scene.post_process.tone_mapping as i32);
+            self.post_shader.setFloat("exposure", scene.post_process.exposure);
+            self.post_shader.setInt("auto_exposure", scene.post_process.auto_exposure as i32);
+            self.post_shader.setInt("adapted_luminance", 1);
+            self.post_shader.setInt("bloom", bloom.enabled as i32);
+            self.post_shader.setFloat("bloom_intensity", bloom.intensity);
+            self.post_shader.setInt("bloom_texture", 2);
+            gl::ActiveTexture(gl::TEXTURE2);
+            gl::BindTexture(gl::TEXTURE_2D, self.bloom.texture());
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, self.auto_exposure.texture());
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, hdr_texture);
+            gl::BindVertexArray(self.quad_vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            gl::BindVertexArray(0);
+
+            // FXAA runs on the tone mapped image, before effects such as film grain that it would smear
+            if fxaa {
+                let target = if effects { &self.fxaa_target } else { &self.output_target };
+                self.fxaa.apply(self.ldr_target.texture, &self.effect_targets, target, self.quad_vao, scene.time);
+            }
+            if effects {
+                let input = if fxaa { &self.fxaa_target } else { &self.ldr_target };
+                scene.effects.apply(input.texture, &self.effect_targets, &self.output_target, self.quad_vao, scene.time);
+            }
+
+            gl::Enable(gl::DEPTH_TEST);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Fits the shadow maps to this frame's camera and lights and draws the shadow casters into them, before
+    /// anything samples them.
+    unsafe fn render_shadows(&mut self, scene: &Scene, camera: &Camera, lights: &[Light], lamp_positions: &[Vector3<f32>]){
+        let light_direction = lights.iter().find_map(|light| match light{
+            Light::Directional(light) => Some(light.direction),
+            _ => None,
+        }).unwrap_or_else(|| vec3(1.0, -10.0, 0.0));
+        gl::Enable(gl::DEPTH_TEST);
+
+        self.shadow_map.update(camera, light_direction);
+        for (i, cascade) in self.shadow_map.cascades.iter().enumerate(){
+            self.shadow_map.bind_cascade(i);
+            for shader in [&self.shadow_shader, &self.instanced_shadow_shader].iter(){
+                shader.useProgram();
+                shader.setMat4("lightspace_transform", cascade.transform);
+            }
+            self.draw_shadow_casters(scene, &self.shadow_shader, &self.instanced_shadow_shader, lamp_positions, None);
+        }
+
+        // Each light's own lamp would enclose it, so it only sees the others
+        self.point_shadows.bind();
+        self.point_shadows.lights = lamp_positions.len().min(MAX_SHADOWED_POINT_LIGHTS);
+        for (i, light_position) in lamp_positions.iter().take(MAX_SHADOWED_POINT_LIGHTS).enumerate(){
+            for shader in [&self.point_shadow_shader, &self.instanced_point_shadow_shader].iter(){
+                shader.useProgram();
+                shader.setUniform3f("light_pos", (light_position.x, light_position.y, light_position.z));
+                shader.setInt("first_layer", 6 * i as i32);
+            }
+            self.draw_shadow_casters(scene, &self.point_shadow_shader, &self.instanced_point_shadow_shader, lamp_positions, Some(i));
+        }
+
+        self.spot_shadows.update(lights, camera, camera.far);
+        self.spot_shadows.bind();
+        for (i, shadow) in self.spot_shadows.shadows.iter().enumerate(){
+            self.spot_shadows.bind_tile(i);
+            for shader in [&self.shadow_shader, &self.instanced_shadow_shader].iter(){
+                shader.useProgram();
+                shader.setMat4("lightspace_transform", shadow.transform);
+            }
+            self.draw_shadow_casters(scene, &self.shadow_shader, &self.instanced_shadow_shader, lamp_positions, None);
+        }
+    }
+
+    /// Draws the shadow casters into the bound shadow map: models and lamps flagged to cast shadows with
+    /// `shader`, and the cube grid, if it casts them, with `instanced_shader`. Both must have the light's uniforms
+    /// set. `skip_lamp` leaves out the lamp at that index in `lamp_positions`.
+    unsafe fn draw_shadow_casters(&self, scene: &Scene, shader: &Shader, instanced_shader: &Shader, lamp_positions: &[Vector3<f32>], skip_lamp: Option<usize>){
+        shader.useProgram();
+        for (model, transform) in scene.graph.shadow_casters(){
+            shader.setMat4("u_model", transform);
+            model.draw(shader);
+        }
+
+        for (i, &position) in lamp_positions.iter().enumerate(){
+            if !scene.lamps_cast_shadows || skip_lamp == Some(i) {
+                continue;
+            }
+            let model = Matrix4::<f32>::from_translation(position)*Matrix4::<f32>::from_scale(0.2);
+            shader.setMat4("u_model", model);
+
+            self.lamp.draw(shader);
+        }
+
+        if scene.cube_grid && scene.cube_grid_casts_shadows {
+            instanced_shader.useProgram();
+            gl::BindVertexArray(self.grid_vao);
+            gl::DrawArraysInstanced(gl::TRIANGLES, 0, 36, self.grid_instances);
+            gl::BindVertexArray(0);
+        }
+    }
+
+    /// Shades models as they are drawn into the main target.
+    unsafe fn draw_forward(&self, scene: &Scene, camera: &Camera){
+        if scene.ssao.enabled {
+            self.ssao.render_forward(scene, self.quad_vao, &scene.ssao);
+            self.bind_ssao();
+            self.ms_target.bind();
+        }
+
+        let shader = &self.lit_shader;
+        shader.useProgram();
+
+        shader.setInt("point_lights", 6);
+        shader.setInt("clusters", 7);
+        shader.setInt("light_indices", 8);
+        shader.setUniform3i("cluster_grid", (CLUSTER_GRID[0] as i32, CLUSTER_GRID[1] as i32, CLUSTER_GRID[2] as i32));
+        shader.setUniform2f("screen_size", (self.config.width as f32, self.config.height as f32));
+        shader.setFloat("cluster_near", camera.near);
+        shader.setFloat("cluster_far", camera.far);
+        shader.setInt("cluster_heatmap", self.cluster_heatmap as i32);
+
+        shader.setUniform3f("camera_pos", (camera.pos.x, camera.pos.y, camera.pos.z));
+        shader.setFloat("time", scene.time);
+        set_shadow_uniforms(shader, &self.shadow_map, &self.point_shadows, &scene.shadows, camera);
+        set_environment_uniforms(shader, scene);
+        set_ssao_uniforms(shader, scene);
+
+        for (model, transform, receive_shadows) in scene.graph.shadow_receivers(){
+            shader.setMat4("u_model", transform);
+            shader.setInt("receive_shadows", receive_shadows as i32);
+            model.draw(shader);
+        }
+    }
+
+    /// Draws models into the G-buffer, then adds each light's contribution to the main target: directional lights
+    /// over the whole screen, point lights inside spheres and spot lights inside cones.
+    unsafe fn draw_deferred(&self, scene: &Scene, camera: &Camera, lights: &[Light]){
+        let gbuffer = self.gbuffer.as_ref().expect("deferred pipeline without a G-buffer");
+
+        gbuffer.bind();
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        // Blending would mix positions and normals with the cleared zeros
+        gl::Disable(gl::BLEND);
+
+        self.gbuffer_shader.useProgram();
+        for (model, transform, receive_shadows) in scene.graph.shadow_receivers(){
+            self.gbuffer_shader.setMat4("u_model", transform);
+            self.gbuffer_shader.setInt("receive_shadows", receive_shadows as i32);
+            model.draw(&self.gbuffer_shader);
+        }
+
+        if scene.ssao.enabled {
+            self.ssao.render(gbuffer.textures[0], gbuffer.textures[1], self.quad_vao, &scene.ssao);
+            self.bind_ssao();
+        }
+
+        // Later forward passes and the light volumes are depth tested against the G-buffer's geometry
+        gbuffer.blit_depth(self.ms_target.id);
+        self.ms_target.bind();
+
+        for (i, &texture) in gbuffer.textures.iter().enumerate(){
+            gl::ActiveTexture(gl::TEXTURE0 + i as u32);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+
+        gl::DepthMask(gl::FALSE);
+
+        for shader in [&self.deferred_quad_shader, &self.deferred_volume_shader].iter(){
+            shader.useProgram();
+            shader.setInt("g_position", 0);
+            shader.setInt("g_normal", 1);
+            shader.setInt("g_albedo_ao", 2);
+            shader.setInt("g_metallic_roughness", 3);
+            shader.setInt("g_emissive", 4);
+            shader.setInt("point_lights", 6);
+            set_shadow_uniforms(shader, &self.shadow_map, &self.point_shadows, &scene.shadows, camera);
+            shader.setUniform2f("screen_size", (self.config.width as f32, self.config.height as f32));
+            shader.setUniform3f("camera_pos", (camera.pos.x, camera.pos.y, camera.pos.z));
+            set_environment_uniforms(shader, scene);
+            set_ssao_uniforms(shader, scene);
+        }
+
+        let (mut directional, mut point, mut spot) = (0, 0, 0);
+
+        // Emissive and environment lighting replace the clear colour wherever there is geometry, and every light
+        // is added on top
+        gl::Disable(gl::DEPTH_TEST);
+        self.deferred_quad_shader.useProgram();
+        self.deferred_quad_shader.setInt("light_type", 3);
+        gl::BindVertexArray(self.quad_vao);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::ONE, gl::ONE);
+        self.deferred_quad_shader.setInt("light_type", 0);
+        for light in lights.iter(){
+            if let Light::Directional(_) = light {
+                self.deferred_quad_shader.setInt("light_index", directional);
+                gl::DrawArrays(gl::TRIANGLES, 0, 6);
+                directional += 1;
+            }
+        }
+
+        // Drawing only the back faces of a volume, where they are behind the geometry, keeps lighting
+        // correct when the camera is inside the volume
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DepthFunc(if camera.reversed_z() { gl::LEQUAL } else { gl::GEQUAL });
+        gl::Enable(gl::CULL_FACE);
+        gl::CullFace(gl::FRONT);
+
+        let shader = &self.deferred_volume_shader;
+        shader.useProgram();
+        for light in lights.iter(){
+            match light{
+                Light::Point(light) => {
+                    let radius = light.range().min(camera.far) * VOLUME_SCALE;
+                    shader.setInt("light_type", 1);
+                    shader.setInt("light_index", point);
+                    shader.setMat4("u_model", Matrix4::from_translation(light.position) * Matrix4::from_scale(radius));
+                    gl::BindVertexArray(self.sphere_vao);
+                    gl::DrawArrays(gl::TRIANGLES, 0, self.sphere_vertices);
+                    point += 1;
+                },
+                Light::Spot(light) => {
+                    let (position, direction) = light.placement(camera);
+                    // Spot lights don't fade with distance, so the cone reaches the far plane
+                    let radius = camera.far * light.outer_cutoff.to_radians().tan() * VOLUME_SCALE;
+                    let rotation = Quaternion::from_arc(vec3(0.0, 0.0, -1.0), direction.normalize(), None);
+                    shader.setInt("light_type", 2);
+                    shader.setInt("light_index", spot);
+                    shader.setMat4("u_model", Matrix4::from_translation(position) * Matrix4::from(rotation) * Matrix4::from_nonuniform_scale(radius, radius, camera.far));
+                    gl::BindVertexArray(self.cone_vao);
+                    gl::DrawArrays(gl::TRIANGLES, 0, self.cone_vertices);
+                    spot += 1;
+                },
+                Light::Directional(_) => {},
+            }
+        }
+        gl::BindVertexArray(0);
+
+        gl::Disable(gl::CULL_FACE);
+        gl::DepthFunc(if camera.reversed_z() { gl::GEQUAL } else { gl::LEQUAL });
+        gl::DepthMask(gl::TRUE);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    }
+
+    unsafe fn bind_ssao(&self){
+        gl::ActiveTexture(gl::TEXTURE12);
+        gl::BindTexture(gl::TEXTURE_2D, self.ssao.texture());
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+
+    /// Copies the last rendered frame to the window.
+    pub fn present(&self){
+        unsafe {
+            self.output_target.blit(0, self.config.width, self.config.height);
+        }
+    }
+
+    /// Reads back a render target from the last rendered frame.
+    pub fn capture(&self, target: RenderTarget) -> Capture{
+        let (width, height) = (self.config.width as u32, self.config.height as u32);
+
+        unsafe {
+            match target {
+                RenderTarget::Final => Capture::read_color(self.output_target.id, width, height, false),
+                RenderTarget::Scene => Capture::read_color(self.scene_target.id, width, height, true),
+                RenderTarget::Shadow => {
+                    let size = self.shadow_map.size as u32;
+                    Capture::read_depth_array(self.shadow_map.texture, size, size, self.shadow_map.cascades.len() as u32)
+                },
+            }
+        }
+    }
+}
+
+impl Drop for Renderer{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.quad_vao);
+            gl::DeleteVertexArrays(1, &self.skybox_vao);
+            gl::DeleteVertexArrays(1, &self.grid_vao);
+            gl::DeleteVertexArrays(1, &self.sphere_vao);
+            gl::DeleteVertexArrays(1, &self.cone_vao);
+            gl::DeleteBuffers(1, &self.quad_vbo);
+            gl::DeleteBuffers(1, &self.skybox_vbo);
+            gl::DeleteBuffers(1, &self.grid_vbo);
+            gl::DeleteBuffers(1, &self.grid_instance_vbo);
+            gl::DeleteBuffers(1, &self.sphere_vbo);
+            gl::DeleteBuffers(1, &self.cone_vbo);
+            gl::DeleteBuffers(1, &self.ubo);
+            gl::DeleteBuffers(1, &self.light_ubo);
+        }
+    }
+}
+
+/// Image based lighting samplers are always given their own units, even without an environment, because
+/// cube and 2D samplers left on the same unit make draws fail.
+fn set_environment_uniforms(shader: &Shader, scene: &Scene){
+    shader.setInt("has_environment", scene.environment.is_some() as i32);
+    shader.setInt("irradiance_map", 9);
+    shader.setInt("prefiltered_map", 10);
+    shader.setInt("brdf_lut", 11);
+    shader.setFloat("prefiltered_levels", PREFILTER_LEVELS as f32);
+}
+
+/// The cascades are sampled from unit 5, and with depth comparison from unit 15, and selected by view space
+/// depth. The point light faces are sampled from unit 13 and the spot light atlas from unit 14.
+fn set_shadow_uniforms(shader: &Shader, shadow_map: &CascadedShadowMap, point_shadows: &PointShadowMaps, settings: &ShadowSettings, camera: &Camera){
+    shader.setInt("shadow_map", 5);
+    shader.setInt("shadow_map_compare", 15);
+    shader.setInt("shadow_filter", settings.filter as i32);
+    shader.setFloat("shadow_filter_radius", settings.filter_radius);
+    shader.setFloat("shadow_light_size", settings.light_size);
+    shader.setInt("cascade_count", shadow_map.cascades.len() as i32);
+    for (i, cascade) in shadow_map.cascades.iter().enumerate(){
+        shader.setMat4(&format!("cascade_transforms[{}]", i), cascade.transform);
+        shader.setFloat(&format!("cascade_splits[{}]", i), cascade.far);
+    }
+    shader.setMat4("camera_view", camera.get_view());
+
+    shader.setInt("point_shadow_maps", 13);
+    shader.setInt("point_shadow_count", point_shadows.lights as i32);
+    for (i, &transform) in cube_face_transforms().iter().enumerate(){
+        shader.setMat4(&format!("point_shadow_faces[{}]", i), transform);
+    }
+    shader.setFloat("point_shadow_far", POINT_SHADOW_FAR);
+
+    shader.setInt("spot_shadow_atlas", 14);
+    shader.setFloat("spot_shadow_near", SPOT_SHADOW_NEAR);
+    shader.setFloat("spot_shadow_far", camera.far);
+}
+
+/// Ambient occlusion is sampled from unit 12, see `Renderer::bind_ssao`.
+fn set_ssao_uniforms(shader: &Shader, scene: &Scene){
+    shader.setInt("ssao", scene.ssao.enabled as i32);
+    shader.setInt("ssao_map", 12);
+}
+
+fn effect_targets(width: i32, height: i32) -> [Framebuffer; 2]{
+    [Framebuffer::new(width, height, gl::RGBA16F), Framebuffer::new(width, height, gl::RGBA16F)]
+}
+
+fn main_target(config: &RendererConfig, width: i32, height: i32) -> Framebuffer{
+    let samples = config.anti_aliasing.samples();
+    match config.pipeline{
+        // TAA reprojects with the scene's depth
+        _ if config.anti_aliasing == AntiAliasing::Taa => Framebuffer::with_depth_texture(width, height, gl::RGBA16F),
+        Pipeline::Forward if samples > 1 => Framebuffer::multisampled(width, height, samples, gl::RGBA16F),
+        // Depth is copied in from the G-buffer, which only works between single sampled framebuffers
+        Pipeline::Forward | Pipeline::Deferred => Framebuffer::new(width, height, gl::RGBA16F),
+    }
+}
+
+fn gbuffer(config: &RendererConfig, width: i32, height: i32) -> Option<Framebuffer>{
+    match config.pipeline{
+        Pipeline::Forward => None,
+        Pipeline::Deferred => Some(Framebuffer::multiple(width, height, &[gl::RGB16F, gl::RGB16F, gl::RGBA8, gl::RGB8, gl::RGB16F])),
+    }
+}
+
+/// Uploads a triangle list with positions only. Returns the VAO, VBO and vertex count.
+unsafe fn create_positions(vertices: &[f32]) -> (u32, u32, i32){
+    let mut vao = 0;
+    let mut vbo = 0;
+
+    gl::GenVertexArrays(1, &mut vao);
+    gl::GenBuffers(1, &mut vbo);
+
+    gl::BindVertexArray(vao);
+    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+    gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(vertices) as GLsizeiptr, vertices.as_ptr() as *const c_void, gl::STATIC_DRAW);
+    gl::EnableVertexAttribArray(0);
+    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, (3 * mem::size_of::<GLfloat>()) as GLsizei, ptr::null());
+
+    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    gl::BindVertexArray(0);
+
+    (vao, vbo, (vertices.len() / 3) as i32)
+}
+
+/// Unit sphere made of latitude/longitude quads, wound counter-clockwise seen from outside.
+fn sphere_vertices() -> Vec<f32>{
+    const STACKS: usize = 12;
+    const SLICES: usize = 16;
+
+    let point = |i: usize, j: usize| {
+        let theta = std::f32::consts::PI * i as f32 / STACKS as f32;
+        let phi = 2.0 * std::f32::consts::PI * j as f32 / SLICES as f32;
+        [theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()]
+    };
+
+    let mut vertices = vec!();
+    for i in 0..STACKS{
+        for j in 0..SLICES{
+            let (a, b, c, d) = (point(i, j), point(i + 1, j), point(i + 1, j + 1), point(i, j + 1));
+            for p in [a, c, b, a, d, c].iter(){
+                vertices.extend_from_slice(p);
+            }
+        }
+    }
+    vertices
+}
+
+/// Cone with its apex at the origin opening towards -z, with a base of radius 1 at z = -1.
+fn cone_vertices() -> Vec<f32>{
+    const SLICES: usize = 16;
+
+    let rim = |j: usize| {
+        let phi = 2.0 * std::f32::consts::PI * j as f32 / SLICES as f32;
+        [phi.cos(), phi.sin(), -1.0]
+    };
+
+    let mut vertices = vec!();
+    for j in 0..SLICES{
+        for p in [[0.0, 0.0, 0.0], rim(j), rim(j + 1), [0.0, 0.0, -1.0], rim(j + 1), rim(j)].iter(){
+            vertices.extend_from_slice(p);
+        }
+    }
+    vertices
+}
+
+/// Unit cube with positions only, used for the skybox.
+unsafe fn create_skybox_cube() -> (u32, u32){
+    let mut vao = 0;
+    let mut vbo = 0;
+
+    gl::GenVertexArrays(1, &mut vao);
+    gl::GenBuffers(1, &mut vbo);
+
+    let vertices: [f32; 108] = [
+        // positions
+        -1.0,  1.0, -1.0,
+        -1.0, -1.0, -1.0,
+         1.0, -1.0, -1.0,
+         1.0, -1.0, -1.0,
+         1.0,  1.0, -1.0,
+        -1.0,  1.0, -1.0,
+
+        -1.0, -1.0,  1.0,
+        -1.0, -1.0, -1.0,
+        -1.0,  1.0, -1.0,
+        -1.0,  1.0, -1.0,
+        -1.0,  1.0,  1.0,
+        -1.0, -1.0,  1.0,
+
+         1.0, -1.0, -1.0,
+         1.0, -1.0,  1.0,
+         1.0,  1.0,  1.0,
+         1.0,  1.0,  1.0,
+         1.0,  1.0, -1.0,
+         1.0, -1.0, -1.0,
+
+        -1.0, -1.0,  1.0,
+        -1.0,  1.0,  1.0,
+         1.0,  1.0,  1.0,
+         1.0,  1.0,  1.0,
+         1.0, -1.0,  1.0,
+        -1.0, -1.0,  1.0,
+
+        -1.0,  1.0, -1.0,
+         1.0,  1.0, -1.0,
+         1.0,  1.0,  1.0,
+         1.0,  1.0,  1.0,
+        -1.0,  1.0,  1.0,
+        -1.0,  1.0, -1.0,
+
+        -1.0, -1.0, -1.0,
+        -1.0, -1.0,  1.0,
+         1.0, -1.0, -1.0,
+         1.0, -1.0, -1.0,
+        -1.0, -1.0,  1.0,
+         1.0, -1.0,  1.0
+    ];
+
+    gl::BindVertexArray(vao);
+
+    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+    gl::BufferData(gl::ARRAY_BUFFER, (mem::size_of::<GLfloat>() * vertices.len()) as GLsizeiptr, &vertices[0] as *const f32 as *const c_void, gl::STATIC_DRAW);
+    gl::EnableVertexAttribArray(0);
+    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, (3 * mem::size_of::<GLfloat>()) as GLsizei, ptr::null());
+
+    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    gl::BindVertexArray(0);
+
+    (vao, vbo)
+}
+
+/// Screen filling quad with positions and uvs, also used for the window quads.
+unsafe fn create_quad() -> (u32, u32){
+    let mut vao = 0;
+    let mut vbo = 0;
+
+    gl::GenVertexArrays(1, &mut vao);
+    gl::GenBuffers(1, &mut vbo);
+
+    let vertices: [f32; 30] = [
+        -1.0, 1.0, 0.0, 0.0, 1.0,
+        1.0, 1.0, 0.0, 1.0, 1.0,
+        1.0, -1.0, 0.0, 1.0, 0.0,
+        1.0, -1.0, 0.0, 1.0, 0.0,
+        -1.0, -1.0, 0.0, 0.0, 0.0,
+        -1.0, 1.0, 0.0, 0.0, 1.0,
+    ];
+
+    gl::BindVertexArray(vao);
+
+    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+    gl::BufferData(gl::ARRAY_BUFFER, (mem::size_of::<GLfloat>() * vertices.len()) as GLsizeiptr, &vertices[0] as *const f32 as *const c_void, gl::STATIC_DRAW);
+    gl::EnableVertexAttribArray(0);
+    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, (5 * mem::size_of::<GLfloat>()) as GLsizei, ptr::null());
+    gl::EnableVertexAttribArray(1);
+    gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, (5 * mem::size_of::<GLfloat>()) as GLsizei, (3 * mem::size_of::<GLfloat>()) as *const c_void);
+
+    gl::BindVertexArray(0);
+    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+    (vao, vbo)
+}
+
+/// Cube with positions and normals plus a per-instance model matrix for each cube in the grid.
+/// Returns the vertex array, both buffers and the number of instances.
+unsafe fn create_cube_grid() -> (u32, u32, u32, i32){
+    let mut vao = 0;
+    let mut vbo = 0;
+    let mut instance_vbo = 0;
+
+    let vertices: [f32;216] = [
+        -0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
+        0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
+        0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
+        0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
+        -0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
+        -0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
+
+        -0.5, -0.5,  0.5,  0.0,  0.0, 1.0,
+        0.5, -0.5,  0.5,  0.0,  0.0, 1.0,
+        0.5,  0.5,  0.5,  0.0,  0.0, 1.0,
+        0.5,  0.5,  0.5,  0.0,  0.0, 1.0,
+        -0.5,  0.5,  0.5,  0.0,  0.0, 1.0,
+        -0.5, -0.5,  0.5,  0.0,  0.0, 1.0,
+
+        -0.5,  0.5,  0.5, -1.0,  0.0,  0.0,
+        -0.5,  0.5, -0.5, -1.0,  0.0,  0.0,
+        -0.5, -0.5, -0.5, -1.0,  0.0,  0.0,
+        -0.5, -0.5, -0.5, -1.0,  0.0,  0.0,
+        -0.5, -0.5,  0.5, -1.0,  0.0,  0.0,
+        -0.5,  0.5,  0.5, -1.0,  0.0,  0.0,
+
+        0.5,  0.5,  0.5,  1.0,  0.0,  0.0,
+        0.5,  0.5, -0.5,  1.0,  0.0,  0.0,
+        0.5, -0.5, -0.5,  1.0,  0.0,  0.0,
+        0.5, -0.5, -0.5,  1.0,  0.0,  0.0,
+        0.5, -0.5,  0.5,  1.0,  0.0,  0.0,
+        0.5,  0.5,  0.5,  1.0,  0.0,  0.0,
+
+        -0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
+        0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
+        0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
+        0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
+        -0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
+        -0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
+
+        -0.5,  0.5, -0.5,  0.0,  1.0,  0.0,
+        0.5,  0.5, -0.5,  0.0,  1.0,  0.0,
+        0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
+        0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
+        -0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
+        -0.5,  0.5, -0.5,  0.0,  1.0,  0.0
+    ];
+
+    let mut models:Vec<Matrix4<f32>> = vec!();
+
+    for i in 1..100{
+        for j in 1..100{
+            for k in 1..100{
+                models.push(Matrix4::from_translation(Vector3::unit_x()* 2.0*i as f32 + Vector3::unit_y()* 2.0*j as f32 + Vector3::unit_z() * 2.0 * k as f32));
+            }
+        }
+    }
+
+    gl::GenVertexArrays(1, &mut vao);
+    gl::GenBuffers(1, &mut vbo);
+    gl::GenBuffers(1, &mut instance_vbo);
+
+    gl::BindVertexArray(vao);
+    gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+    gl::BufferData(gl::ARRAY_BUFFER, (mem::size_of::<GLfloat>() * vertices.len()) as GLsizeiptr, &vertices[0] as *const f32 as *const c_void, gl::STATIC_DRAW);
+    gl::EnableVertexAttribArray(0);
+    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, (6 * mem::size_of::<GLfloat>()) as GLsizei, ptr::null());
+    gl::EnableVertexAttribArray(1);
+    gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, (6 * mem::size_of::<GLfloat>()) as GLsizei, (3 * mem::size_of::<GLfloat>()) as *const c_void);
+
+    gl::BindBuffer(gl::ARRAY_BUFFER, instance_vbo);
+    gl::BufferData(gl::ARRAY_BUFFER, (mem::size_of::<Matrix4<f32>>() * models.len()) as isize, &models[0] as *const Matrix4<f32> as *const c_void, gl::STATIC_DRAW);
+    for i in 0..4 {
+        gl::EnableVertexAttribArray(2 + i);
+        gl::VertexAttribPointer(2 + i, 4, gl::FLOAT, gl::FALSE, (mem::size_of::<Matrix4<f32>>()) as i32, (i as usize * mem::size_of::<Vector4<f32>>()) as *const c_void);
+        gl::VertexAttribDivisor(2 + i, 1);
+    }
+
+    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    gl::BindVertexArray(0);
+
+    (vao, vbo, instance_vbo, models.len() as i32)
+}
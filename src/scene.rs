@@ -0,0 +1,740 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cgmath::{Quaternion, Vector3, Deg};
+use cgmath::prelude::*;
+use serde::Deserialize;
+
+use crate::ibl::Environment;
+use crate::light::{Attenuation, DirectionalLight, Light, PointLight, ShadowBias, SpotLight, MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use crate::model::Model;
+use crate::post_process::{EffectPass, PostProcessChain, BLUR, EDGE_DETECT, SHARPEN};
+use crate::ssao::MAX_SSAO_SAMPLES;
+use crate::scene_graph::{NodeId, SceneGraph, SceneNode, Transform};
+use crate::texture::CubeMap;
+
+/// A light as written in a scene file.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename = "Light")]
+enum LightDescription{
+    Directional{
+        direction: [f32; 3],
+        ambient: [f32; 3],
+        diffuse: [f32; 3],
+        specular: [f32; 3],
+        #[serde(default)]
+        shadow_bias: ShadowBias,
+    },
+    Point{
+        position: [f32; 3],
+        ambient: [f32; 3],
+        diffuse: [f32; 3],
+        specular: [f32; 3],
+        /// Either explicit falloff terms or a `range` to derive them from.
+        #[serde(default)]
+        attenuation: Option<Attenuation>,
+        #[serde(default)]
+        range: Option<f32>,
+        #[serde(default)]
+        shadow_bias: ShadowBias,
+    },
+    Spot{
+        #[serde(default)]
+        position: [f32; 3],
+        #[serde(default)]
+        direction: [f32; 3],
+        ambient: [f32; 3],
+        diffuse: [f32; 3],
+        specular: [f32; 3],
+        cutoff: f32,
+        outer_cutoff: f32,
+        #[serde(default)]
+        follow_camera: bool,
+        #[serde(default)]
+        cast_shadows: bool,
+        #[serde(default)]
+        shadow_bias: ShadowBias,
+    },
+}
+
+impl LightDescription{
+    fn kind(&self) -> &'static str{
+        match self{
+            LightDescription::Directional{..} => "Directional",
+            LightDescription::Point{..} => "Point",
+            LightDescription::Spot{..} => "Spot",
+        }
+    }
+}
+
+impl From<&LightDescription> for Light{
+    fn from(description: &LightDescription) -> Light{
+        match *description{
+            LightDescription::Directional{ direction, ambient, diffuse, specular, shadow_bias } => Light::Directional(DirectionalLight{
+                direction: direction.into(),
+                ambient: ambient.into(),
+                diffuse: diffuse.into(),
+                specular: specular.into(),
+                shadow_bias,
+            }),
+            LightDescription::Point{ position, ambient, diffuse, specular, attenuation, range, shadow_bias } => Light::Point(PointLight{
+                position: position.into(),
+                ambient: ambient.into(),
+                diffuse: diffuse.into(),
+                specular: specular.into(),
+                attenuation: attenuation.unwrap_or_else(|| Attenuation::for_range(range.unwrap_or(DEFAULT_RANGE))),
+                shadow_bias,
+            }),
+            LightDescription::Spot{ position, direction, ambient, diffuse, specular, cutoff, outer_cutoff, follow_camera, cast_shadows, shadow_bias } => Light::Spot(SpotLight{
+                position: position.into(),
+                direction: direction.into(),
+                ambient: ambient.into(),
+                diffuse: diffuse.into(),
+                specular: specular.into(),
+                cutoff,
+                outer_cutoff,
+                follow_camera,
+                cast_shadows,
+                shadow_bias,
+            }),
+        }
+    }
+}
+
+/// Range of point lights that give neither `attenuation` nor `range`.
+const DEFAULT_RANGE: f32 = 50.0;
+
+/// Translation, rotation as euler angles in degrees applied x then y then z, and scale.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+struct TransformDescription{
+    position: [f32; 3],
+    rotation: [f32; 3],
+    scale: [f32; 3],
+}
+
+impl Default for TransformDescription{
+    fn default() -> Self{
+        TransformDescription{
+            position: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl From<TransformDescription> for Transform{
+    fn from(description: TransformDescription) -> Transform{
+        let [x, y, z] = description.rotation;
+        Transform{
+            translation: description.position.into(),
+            rotation: Quaternion::from_angle_z(Deg(z)) * Quaternion::from_angle_y(Deg(y)) * Quaternion::from_angle_x(Deg(x)),
+            scale: description.scale.into(),
+        }
+    }
+}
+
+/// Camera position and orientation in degrees.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct CameraPose{
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Default for CameraPose{
+    fn default() -> Self{
+        CameraPose{
+            position: [0.0; 3],
+            yaw: 270.0,
+            pitch: 0.0,
+        }
+    }
+}
+
+/// Curve mapping HDR scene colour into the displayable range. The discriminants are the values `postpro.frag` expects.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping{
+    /// Clip everything above 1.
+    None = 0,
+    Reinhard = 1,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces = 2,
+    /// John Hable's filmic curve from Uncharted 2.
+    Uncharted2 = 3,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct BloomSettings{
+    pub enabled: bool,
+    /// Brightness above which pixels start to glow.
+    pub threshold: f32,
+    /// Scale of the glow added to the scene.
+    pub intensity: f32,
+    /// Spread of the blur at each level, in texels.
+    pub radius: f32,
+}
+
+impl Default for BloomSettings{
+    fn default() -> Self{
+        BloomSettings{
+            enabled: true,
+            threshold: 1.0,
+            intensity: 0.2,
+            radius: 1.0,
+        }
+    }
+}
+
+/// How shadows are filtered, for the directional light's cascades as well as point and spot light shadows. The
+/// discriminants are the values the lit shaders expect.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ShadowFilter{
+    /// Depth comparison and bilinear filtering done by the texture unit, the cheapest and hardest edged. Point and spot
+    /// shadow maps, which are read without comparison, get a fixed PCF kernel instead.
+    Hardware = 0,
+    /// A Poisson disk of comparisons, rotated per pixel so the banding turns into noise.
+    Poisson = 1,
+    /// Percentage-closer soft shadows: penumbrae widen with the distance between the shadow and its caster,
+    /// found by a blocker search.
+    Pcss = 2,
+}
+
+impl ShadowFilter{
+    /// The filter after this one, going back to the first after the last.
+    pub fn next(self) -> ShadowFilter{
+        match self{
+            ShadowFilter::Hardware => ShadowFilter::Poisson,
+            ShadowFilter::Poisson => ShadowFilter::Pcss,
+            ShadowFilter::Pcss => ShadowFilter::Hardware,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct ShadowSettings{
+    pub filter: ShadowFilter,
+    /// Radius of the Poisson disk in shadow map texels.
+    pub filter_radius: f32,
+    /// Tangent of the angle a light's disc covers, for PCSS. The sun's is about 0.005.
+    pub light_size: f32,
+}
+
+impl Default for ShadowSettings{
+    fn default() -> Self{
+        ShadowSettings{
+            filter: ShadowFilter::Poisson,
+            filter_radius: 1.5,
+            light_size: 0.02,
+        }
+    }
+}
+
+/// Screen space ambient occlusion, which darkens the ambient lighting in creases and corners.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct SsaoSettings{
+    pub enabled: bool,
+    /// World space radius of the hemisphere searched for occluders.
+    pub radius: f32,
+    /// Depth difference ignored as self occlusion, which removes acne on flat surfaces.
+    pub bias: f32,
+    /// Hemisphere samples per pixel, from 1 to `MAX_SSAO_SAMPLES`.
+    pub samples: usize,
+}
+
+impl Default for SsaoSettings{
+    fn default() -> Self{
+        SsaoSettings{
+            enabled: true,
+            radius: 0.5,
+            bias: 0.025,
+            samples: 32,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct PostProcess{
+    pub tone_mapping: ToneMapping,
+    /// Multiplies scene colour before tone mapping. With `auto_exposure` it acts as exposure compensation.
+    pub exposure: f32,
+    /// Expose for the average scene luminance, adapting gradually as it changes.
+    pub auto_exposure: bool,
+    /// Read every frame, so it can be changed while running.
+    pub bloom: BloomSettings,
+}
+
+impl Default for PostProcess{
+    fn default() -> Self{
+        PostProcess{
+            tone_mapping: ToneMapping::Aces,
+            exposure: 1.0,
+            auto_exposure: false,
+            bloom: BloomSettings::default(),
+        }
+    }
+}
+
+/// An effect of the post-processing chain as written in a scene file.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename = "Effect")]
+enum EffectDescription{
+    EdgeDetect,
+    Sharpen,
+    Blur,
+    /// Row major 3x3 convolution.
+    Kernel{ weights: [f32; 9] },
+    Grayscale,
+    Invert,
+    Vignette{ radius: f32, softness: f32 },
+    ChromaticAberration{ strength: f32 },
+    FilmGrain{ strength: f32 },
+}
+
+impl From<&EffectDescription> for EffectPass{
+    fn from(description: &EffectDescription) -> EffectPass{
+        match *description{
+            EffectDescription::EdgeDetect => EffectPass::kernel("edge_detect", EDGE_DETECT),
+            EffectDescription::Sharpen => EffectPass::kernel("sharpen", SHARPEN),
+            EffectDescription::Blur => EffectPass::kernel("blur", BLUR),
+            EffectDescription::Kernel{ weights } => EffectPass::kernel("kernel", weights),
+            EffectDescription::Grayscale => EffectPass::grayscale(),
+            EffectDescription::Invert => EffectPass::invert(),
+            EffectDescription::Vignette{ radius, softness } => EffectPass::vignette(radius, softness),
+            EffectDescription::ChromaticAberration{ strength } => EffectPass::chromatic_aberration(strength),
+            EffectDescription::FilmGrain{ strength } => EffectPass::film_grain(strength),
+        }
+    }
+}
+
+/// A node in the scene file. Its transform, light and camera are relative to its parent.
+#[derive(Deserialize)]
+struct NodeDescription{
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    transform: TransformDescription,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    light: Option<LightDescription>,
+    #[serde(default)]
+    camera: Option<CameraPose>,
+    /// Whether the node's model is drawn into the shadow maps.
+    #[serde(default = "default_true")]
+    cast_shadows: bool,
+    /// Whether shadows darken the node's model.
+    #[serde(default = "default_true")]
+    receive_shadows: bool,
+    #[serde(default)]
+    children: Vec<NodeDescription>,
+}
+
+fn default_true() -> bool{
+    true
+}
+
+/// An equirectangular `.hdr` panorama to use as the skybox instead of six faces.
+#[derive(Deserialize)]
+struct HdrSkyboxDescription{
+    path: String,
+    /// Width and height of the cube map faces it is projected onto.
+    #[serde(default = "default_face_size")]
+    face_size: i32,
+}
+
+fn default_face_size() -> i32{
+    512
+}
+
+/// Contents of a scene file.
+#[derive(Deserialize)]
+#[serde(rename = "Scene")]
+struct SceneDescription{
+    /// Cube map faces in +x, -x, +y, -y, +z, -z order.
+    #[serde(default)]
+    skybox: Option<Vec<String>>,
+    #[serde(default)]
+    hdr_skybox: Option<HdrSkyboxDescription>,
+    #[serde(default)]
+    nodes: Vec<NodeDescription>,
+    /// Positions of alpha blended window quads.
+    #[serde(default)]
+    windows: Vec<[f32; 3]>,
+    #[serde(default)]
+    cube_grid: bool,
+    /// Whether the cube grid is drawn into the shadow maps. Off unless asked for, since every one of its
+    /// instances would be drawn into every cascade, cube face and atlas tile.
+    #[serde(default)]
+    cube_grid_casts_shadows: bool,
+    /// Whether the point lights' lamp cubes are drawn into the shadow maps.
+    #[serde(default = "default_true")]
+    lamps_cast_shadows: bool,
+    #[serde(default)]
+    shadows: ShadowSettings,
+    #[serde(default)]
+    ssao: SsaoSettings,
+    #[serde(default)]
+    post_process: PostProcess,
+    /// Post-processing effects applied after tone mapping, in order.
+    #[serde(default)]
+    effects: Vec<EffectDescription>,
+}
+
+#[derive(Debug)]
+pub enum SceneError{
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ron::Error),
+    /// An entry in the scene file failed validation. `entry` names it, e.g. `nodes[0].children[2].light (Point)`.
+    Invalid{ entry: String, message: String },
+}
+
+impl fmt::Display for SceneError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            SceneError::Io(path, err) => write!(f, "failed to read {}: {}", path.display(), err),
+            SceneError::Parse(path, err) => write!(f, "failed to parse {}: {}", path.display(), err),
+            SceneError::Invalid{ entry, message } => write!(f, "{}: {}", entry, message),
+        }
+    }
+}
+
+impl Error for SceneError{}
+
+fn invalid(entry: String, message: &str) -> SceneError{
+    SceneError::Invalid{ entry, message: message.into() }
+}
+
+/// Everything drawn by the `Renderer` in one frame.
+pub struct Scene{
+    /// Seconds since the start of the scene, passed to shaders for animation.
+    pub time: f32,
+    pub skybox: Option<CubeMap>,
+    /// Ambient lighting precomputed from the skybox.
+    pub environment: Option<Environment>,
+    /// Models, lights and the camera start. Models are drawn with the lit shader and point lights are also
+    /// drawn as small outlined lamp cubes.
+    pub graph: SceneGraph,
+    /// Alpha blended window quads, drawn back to front.
+    pub window_positions: Vec<Vector3<f32>>,
+    /// Draw the instanced cube grid.
+    pub cube_grid: bool,
+    /// The grid and lamps are unlit, so unlike scene graph nodes they only have a flag for casting shadows.
+    pub cube_grid_casts_shadows: bool,
+    pub lamps_cast_shadows: bool,
+    /// Read every frame, like `ssao`, so it can be changed while running.
+    pub shadows: ShadowSettings,
+    /// Read every frame, so it can be changed while running.
+    pub ssao: SsaoSettings,
+    pub post_process: PostProcess,
+    pub effects: PostProcessChain,
+}
+
+impl Default for Scene{
+    fn default() -> Self{
+        Scene{
+            time: 0.0,
+            skybox: None,
+            environment: None,
+            graph: SceneGraph::default(),
+            window_positions: vec!(),
+            cube_grid: false,
+            cube_grid_casts_shadows: false,
+            lamps_cast_shadows: true,
+            shadows: ShadowSettings::default(),
+            ssao: SsaoSettings::default(),
+            post_process: PostProcess::default(),
+            effects: PostProcessChain::default(),
+        }
+    }
+}
+
+impl Scene{
+    /// Loads a RON scene file, validating it before loading any models or textures.
+    /// Paths inside the file are relative to the working directory, like shader paths.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError>{
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|err| SceneError::Io(path.into(), err))?;
+        let description: SceneDescription = ron::de::from_str(&source).map_err(|err| SceneError::Parse(path.into(), err))?;
+
+        validate(&description)?;
+
+        let skybox = match (&description.skybox, &description.hdr_skybox){
+            (Some(faces), _) => Some(CubeMap::from_faces(faces).map_err(|err| invalid("skybox".into(), &err))?),
+            (None, Some(hdr)) => Some(CubeMap::from_equirectangular(&hdr.path, hdr.face_size).map_err(|err| invalid("hdr_skybox".into(), &err))?),
+            (None, None) => None,
+        };
+        let environment = skybox.as_ref().map(Environment::from_cube_map);
+
+        let mut graph = SceneGraph::default();
+        let root = graph.root();
+        for (i, node) in description.nodes.into_iter().enumerate(){
+            add_node(&mut graph, root, node, &format!("nodes[{}]", i))?;
+        }
+        graph.update();
+
+        Ok(Scene{
+            time: 0.0,
+            skybox,
+            environment,
+            graph,
+            window_positions: description.windows.iter().map(|&w| w.into()).collect(),
+            cube_grid: description.cube_grid,
+            cube_grid_casts_shadows: description.cube_grid_casts_shadows,
+            lamps_cast_shadows: description.lamps_cast_shadows,
+            shadows: description.shadows,
+            ssao: description.ssao,
+            post_process: description.post_process,
+            effects: PostProcessChain{ passes: description.effects.iter().map(EffectPass::from).collect() },
+        })
+    }
+
+    /// Recomputes world transforms after nodes have moved. Call before rendering.
+    pub fn update(&mut self){
+        self.graph.update();
+    }
+
+    /// Where the camera starts: the first camera component, or the origin looking down -z.
+    pub fn camera(&self) -> CameraPose{
+        self.graph.camera().unwrap_or_default()
+    }
+
+    /// Lights in world space.
+    pub fn lights(&self) -> Vec<Light>{
+        self.graph.lights().collect()
+    }
+
+    pub fn point_light_positions(&self) -> Vec<Vector3<f32>>{
+        self.graph.lights().filter_map(|light| match light{
+            Light::Point(light) => Some(light.position),
+            _ => None,
+        }).collect()
+    }
+}
+
+/// Adds a validated node and its children. `entry` names the node in errors, like `validate_node`.
+fn add_node(graph: &mut SceneGraph, parent: NodeId, description: NodeDescription, entry: &str) -> Result<(), SceneError>{
+    let mut node = SceneNode::new(&description.name, description.transform.into());
+    node.model = match &description.model{
+        Some(path) => Some(Model::new(path).map_err(|err| invalid(format!("{}.model ({})", entry, path), &err))?),
+        None => None,
+    };
+    node.light = description.light.as_ref().map(Light::from);
+    node.camera = description.camera;
+    node.cast_shadows = description.cast_shadows;
+    node.receive_shadows = description.receive_shadows;
+
+    let id = graph.add(parent, node);
+    for (i, child) in description.children.into_iter().enumerate(){
+        add_node(graph, id, child, &format!("{}.children[{}]", entry, i))?;
+    }
+    Ok(())
+}
+
+fn validate(description: &SceneDescription) -> Result<(), SceneError>{
+    if let Some(faces) = &description.skybox{
+        if faces.len() != 6 {
+            return Err(invalid("skybox".into(), &format!("expected 6 faces, got {}", faces.len())));
+        }
+        for (i, face) in faces.iter().enumerate(){
+            if !Path::new(face).is_file() {
+                return Err(invalid(format!("skybox[{}] ({})", i, face), "image file not found"));
+            }
+        }
+    }
+
+    if let Some(hdr) = &description.hdr_skybox{
+        let entry = format!("hdr_skybox ({})", hdr.path);
+        if description.skybox.is_some() {
+            return Err(invalid(entry, "a scene can't have both a skybox and an hdr_skybox"));
+        }
+        if !Path::new(&hdr.path).is_file() {
+            return Err(invalid(entry, "image file not found"));
+        }
+        if hdr.face_size <= 0 {
+            return Err(invalid(entry, "face_size must be positive"));
+        }
+    }
+
+    if description.shadows.filter_radius <= 0.0 {
+        return Err(invalid("shadows".into(), "filter_radius must be positive"));
+    }
+    if description.shadows.light_size < 0.0 {
+        return Err(invalid("shadows".into(), "light_size must not be negative"));
+    }
+
+    if !(1..=MAX_SSAO_SAMPLES).contains(&description.ssao.samples) {
+        return Err(invalid("ssao".into(), &format!("samples must be between 1 and {}", MAX_SSAO_SAMPLES)));
+    }
+
+    let mut counts = LightCounts::default();
+    for (i, node) in description.nodes.iter().enumerate(){
+        validate_node(node, &format!("nodes[{}]", i), &mut counts)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct LightCounts{
+    directional: usize,
+    point: usize,
+    spot: usize,
+}
+
+fn validate_node(node: &NodeDescription, entry: &str, counts: &mut LightCounts) -> Result<(), SceneError>{
+    if node.transform.scale.contains(&0.0) {
+        return Err(invalid(entry.into(), "scale must not be zero"));
+    }
+
+    if let Some(path) = &node.model{
+        if !Path::new(path).is_file() {
+            return Err(invalid(format!("{}.model ({})", entry, path), "model file not found"));
+        }
+    }
+
+    if let Some(light) = &node.light{
+        validate_light(light, format!("{}.light ({})", entry, light.kind()), counts)?;
+    }
+
+    for (i, child) in node.children.iter().enumerate(){
+        validate_node(child, &format!("{}.children[{}]", entry, i), counts)?;
+    }
+
+    Ok(())
+}
+
+fn validate_light(light: &LightDescription, entry: String, counts: &mut LightCounts) -> Result<(), SceneError>{
+    let colours = match light{
+        LightDescription::Directional{ ambient, diffuse, specular, .. }
+        | LightDescription::Point{ ambient, diffuse, specular, .. }
+        | LightDescription::Spot{ ambient, diffuse, specular, .. } => [ambient, diffuse, specular],
+    };
+    if colours.iter().any(|c| c.iter().any(|&v| v < 0.0)) {
+        return Err(invalid(entry, "colours must not be negative"));
+    }
+    let bias = match light{
+        LightDescription::Directional{ shadow_bias, .. }
+        | LightDescription::Point{ shadow_bias, .. }
+        | LightDescription::Spot{ shadow_bias, .. } => shadow_bias,
+    };
+    if bias.constant < 0.0 || bias.slope < 0.0 || bias.normal_offset < 0.0 {
+        return Err(invalid(entry, "shadow_bias terms must not be negative"));
+    }
+
+    match light{
+        LightDescription::Directional{ direction, .. } => {
+            counts.directional += 1;
+            if counts.directional > MAX_DIRECTIONAL_LIGHTS {
+                return Err(invalid(entry, &format!("at most {} directional lights are supported", MAX_DIRECTIONAL_LIGHTS)));
+            }
+            if *direction == [0.0; 3] {
+                return Err(invalid(entry, "direction must not be zero"));
+            }
+        },
+        LightDescription::Point{ attenuation, range, .. } => {
+            counts.point += 1;
+            if counts.point > MAX_POINT_LIGHTS {
+                return Err(invalid(entry, &format!("at most {} point lights are supported", MAX_POINT_LIGHTS)));
+            }
+            match (attenuation, range){
+                (Some(_), Some(_)) => return Err(invalid(entry, "give either attenuation or range, not both")),
+                (Some(Attenuation{ constant, linear, quadratic }), None)
+                    if *constant < 0.0 || *linear < 0.0 || *quadratic < 0.0 || constant + linear + quadratic <= 0.0 => {
+                    return Err(invalid(entry, "attenuation terms must not be negative and must not all be zero"));
+                },
+                (None, Some(range)) if *range <= 0.0 => return Err(invalid(entry, "range must be positive")),
+                _ => {},
+            }
+        },
+        LightDescription::Spot{ direction, cutoff, outer_cutoff, follow_camera, .. } => {
+            counts.spot += 1;
+            if counts.spot > MAX_SPOT_LIGHTS {
+                return Err(invalid(entry, &format!("at most {} spot lights are supported", MAX_SPOT_LIGHTS)));
+            }
+            if !follow_camera && *direction == [0.0; 3] {
+                return Err(invalid(entry, "direction must not be zero"));
+            }
+            if !(0.0 < *cutoff && cutoff <= outer_cutoff && *outer_cutoff < 90.0) {
+                return Err(invalid(entry, "cutoff angles must satisfy 0 < cutoff <= outer_cutoff < 90"));
+            }
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn parse(source: &str) -> Result<(), SceneError>{
+        let description: SceneDescription = ron::de::from_str(source).map_err(|err| SceneError::Parse("test.ron".into(), err))?;
+        validate(&description)
+    }
+
+    #[test]
+    fn scene_files_are_valid(){
+        for path in ["scenes/default.ron", "tests/scenes/corona.ron", "tests/scenes/cube_grid.ron", "tests/scenes/skybox.ron"].iter(){
+            let source = fs::read_to_string(path).unwrap();
+            if let Err(err) = parse(&source) {
+                panic!("{}: {}", path, err);
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_lamps_cast_shadows_by_default(){
+        let description: SceneDescription = ron::de::from_str("Scene(cube_grid: true)").unwrap();
+        assert!(!description.cube_grid_casts_shadows);
+        assert!(description.lamps_cast_shadows);
+
+        let description: SceneDescription = ron::de::from_str("Scene(cube_grid_casts_shadows: true, lamps_cast_shadows: false)").unwrap();
+        assert!(description.cube_grid_casts_shadows);
+        assert!(!description.lamps_cast_shadows);
+    }
+
+    #[test]
+    fn errors_name_the_offending_entry(){
+        let err = parse(r#"Scene(
+            nodes: [
+                (light: Some(Directional(direction: (0.0, -1.0, 0.0), ambient: (0.1, 0.1, 0.1), diffuse: (1.0, 1.0, 1.0), specular: (1.0, 1.0, 1.0)))),
+                (children: [
+                    (transform: (position: (0.0, 1.0, 0.0))),
+                    (light: Some(Point(
+                        position: (0.0, 1.0, 0.0),
+                        ambient: (0.1, 0.1, 0.1), diffuse: (1.0, 1.0, 1.0), specular: (1.0, 1.0, 1.0),
+                        attenuation: Some((constant: 0.0, linear: 0.0, quadratic: 0.0)),
+                    ))),
+                ]),
+            ],
+        )"#).unwrap_err();
+        assert_eq!(err.to_string(), "nodes[1].children[1].light (Point): attenuation terms must not be negative and must not all be zero");
+
+        let err = parse(r#"Scene(nodes: [(model: Some("models/missing.obj"))])"#).unwrap_err();
+        assert_eq!(err.to_string(), "nodes[0].model (models/missing.obj): model file not found");
+
+        let err = parse(r#"Scene(hdr_skybox: Some((path: "textures/missing.hdr", face_size: 256)))"#).unwrap_err();
+        assert_eq!(err.to_string(), "hdr_skybox (textures/missing.hdr): image file not found");
+
+        let err = parse("Scene(ssao: (samples: 0))").unwrap_err();
+        assert_eq!(err.to_string(), "ssao: samples must be between 1 and 64");
+
+        let err = parse("Scene(shadows: (filter: Pcss, filter_radius: 0.0))").unwrap_err();
+        assert_eq!(err.to_string(), "shadows: filter_radius must be positive");
+
+        let err = parse(r#"Scene(nodes: [(light: Some(Directional(
+            direction: (0.0, -1.0, 0.0), ambient: (0.1, 0.1, 0.1), diffuse: (1.0, 1.0, 1.0), specular: (1.0, 1.0, 1.0),
+            shadow_bias: (slope: -1.0),
+        )))])"#).unwrap_err();
+        assert_eq!(err.to_string(), "nodes[0].light (Directional): shadow_bias terms must not be negative");
+    }
+}
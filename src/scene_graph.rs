@@ -0,0 +1,316 @@
+use cgmath::{Matrix4, Point3, Quaternion, Vector3, vec3};
+use cgmath::prelude::*;
+use cgmath::Transform as _;
+
+use crate::model::Model;
+use crate::light::Light;
+use crate::scene::CameraPose;
+
+/// Index of a node in its `SceneGraph`.
+pub type NodeId = usize;
+
+/// Local translation, rotation and scale of a node relative to its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform{
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform{
+    fn default() -> Self{
+        Transform{
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform{
+    pub fn matrix(&self) -> Matrix4<f32>{
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+pub struct SceneNode{
+    pub name: String,
+    /// Model drawn with the node's world transform.
+    pub model: Option<Model>,
+    /// Light whose position and direction are relative to the node.
+    pub light: Option<Light>,
+    /// Marks the node the camera starts from, facing along the pose's yaw and pitch.
+    pub camera: Option<CameraPose>,
+    /// Draw the model into the shadow maps.
+    pub cast_shadows: bool,
+    /// Darken the model where shadows fall on it.
+    pub receive_shadows: bool,
+
+    transform: Transform,
+    world: Matrix4<f32>,
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl SceneNode{
+    pub fn new(name: &str, transform: Transform) -> SceneNode{
+        SceneNode{
+            name: name.into(),
+            model: None,
+            light: None,
+            camera: None,
+            cast_shadows: true,
+            receive_shadows: true,
+            transform,
+            world: Matrix4::identity(),
+            dirty: true,
+            parent: None,
+            children: vec!(),
+        }
+    }
+
+    pub fn transform(&self) -> &Transform{
+        &self.transform
+    }
+
+    /// Replaces the local transform. The world transforms of the node and its descendants are
+    /// recomputed by the next `SceneGraph::update`.
+    pub fn set_transform(&mut self, transform: Transform){
+        self.transform = transform;
+        self.dirty = true;
+    }
+
+    pub fn set_translation(&mut self, translation: Vector3<f32>){
+        self.transform.translation = translation;
+        self.dirty = true;
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>){
+        self.transform.rotation = rotation;
+        self.dirty = true;
+    }
+
+    pub fn set_scale(&mut self, scale: Vector3<f32>){
+        self.transform.scale = scale;
+        self.dirty = true;
+    }
+
+    /// World transform as of the last `SceneGraph::update`.
+    pub fn world_transform(&self) -> Matrix4<f32>{
+        self.world
+    }
+
+    pub fn parent(&self) -> Option<NodeId>{
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId]{
+        &self.children
+    }
+}
+
+/// A tree of nodes stored in a flat list, with a root node at index 0.
+pub struct SceneGraph{
+    nodes: Vec<SceneNode>,
+}
+
+impl Default for SceneGraph{
+    fn default() -> Self{
+        SceneGraph{
+            nodes: vec!(SceneNode::new("root", Transform::default())),
+        }
+    }
+}
+
+impl SceneGraph{
+    pub fn root(&self) -> NodeId{
+        0
+    }
+
+    /// Adds `node` as the last child of `parent` and returns its id.
+    pub fn add(&mut self, parent: NodeId, mut node: SceneNode) -> NodeId{
+        let id = self.nodes.len();
+        node.parent = Some(parent);
+        node.dirty = true;
+        self.nodes.push(node);
+        self.nodes[parent].children.push(id);
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &SceneNode{
+        &self.nodes[id]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut SceneNode{
+        &mut self.nodes[id]
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId>{
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SceneNode>{
+        self.nodes.iter()
+    }
+
+    /// Recomputes the world transforms of dirty nodes and everything below them.
+    pub fn update(&mut self){
+        let mut stack = vec!((self.root(), false));
+
+        while let Some((id, parent_changed)) = stack.pop(){
+            let changed = parent_changed || self.nodes[id].dirty;
+
+            if changed {
+                let parent_world = match self.nodes[id].parent{
+                    Some(parent) => self.nodes[parent].world,
+                    None => Matrix4::identity(),
+                };
+                let node = &mut self.nodes[id];
+                node.world = parent_world * node.transform.matrix();
+                node.dirty = false;
+            }
+
+            stack.extend(self.nodes[id].children.iter().map(|&child| (child, changed)));
+        }
+    }
+
+    /// Models with their world transforms.
+    pub fn models(&self) -> impl Iterator<Item = (&Model, Matrix4<f32>)>{
+        self.nodes.iter().filter_map(|node| node.model.as_ref().map(|model| (model, node.world)))
+    }
+
+    /// Models with their world transforms and whether shadows fall on them.
+    pub fn shadow_receivers(&self) -> impl Iterator<Item = (&Model, Matrix4<f32>, bool)>{
+        self.nodes.iter().filter_map(|node| node.model.as_ref().map(|model| (model, node.world, node.receive_shadows)))
+    }
+
+    /// Opposite corners of the world space box around the node's model, `None` when it has none.
+    pub fn node_bounds(&self, id: NodeId) -> Option<(Point3<f32>, Point3<f32>)>{
+        let node = &self.nodes[id];
+        node.model.as_ref()?.bounds().map(|bounds| world_bounds(bounds, node.world))
+    }
+
+    /// Opposite corners of the world space box around every model, `None` when there are none.
+    pub fn model_bounds(&self) -> Option<(Point3<f32>, Point3<f32>)>{
+        (0..self.nodes.len()).filter_map(|id| self.node_bounds(id)).fold(None, |bounds, (min, max)| Some(match bounds{
+            Some((all_min, all_max)) => (point_min(all_min, min), point_max(all_max, max)),
+            None => (min, max),
+        }))
+    }
+
+    /// The first node with a model after `after`, or after the root when `after` is `None`. `None` once there are no
+    /// more, so cycling through models can return to the whole scene.
+    pub fn next_model(&self, after: Option<NodeId>) -> Option<NodeId>{
+        let start = after.map_or(0, |id| id + 1);
+        (start..self.nodes.len()).find(|&id| self.nodes[id].model.is_some())
+    }
+
+    /// Models that cast shadows, with their world transforms.
+    pub fn shadow_casters(&self) -> impl Iterator<Item = (&Model, Matrix4<f32>)>{
+        self.nodes.iter()
+            .filter(|node| node.cast_shadows)
+            .filter_map(|node| node.model.as_ref().map(|model| (model, node.world)))
+    }
+
+    /// Lights with positions and directions moved into world space.
+    pub fn lights(&self) -> impl Iterator<Item = Light> + '_{
+        self.nodes.iter().filter_map(|node| node.light.as_ref().map(|light| {
+            let point = |p: Vector3<f32>| node.world.transform_point(Point3::from_vec(p)).to_vec();
+            let vector = |v: Vector3<f32>| node.world.transform_vector(v);
+
+            let mut light = *light;
+            match &mut light{
+                Light::Directional(light) => light.direction = vector(light.direction),
+                Light::Point(light) => light.position = point(light.position),
+                Light::Spot(light) => {
+                    light.position = point(light.position);
+                    light.direction = vector(light.direction);
+                },
+            }
+            light
+        }))
+    }
+
+    /// The first camera component, with its node's world position.
+    pub fn camera(&self) -> Option<CameraPose>{
+        self.nodes.iter().find_map(|node| node.camera.map(|pose| {
+            let position = node.world.transform_point(Point3::from(pose.position));
+            CameraPose{ position: position.into(), ..pose }
+        }))
+    }
+}
+
+fn point_min(a: Point3<f32>, b: Point3<f32>) -> Point3<f32>{
+    Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
+fn point_max(a: Point3<f32>, b: Point3<f32>) -> Point3<f32>{
+    Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+}
+
+/// The world space box around a model space box from `min` to `max` placed with `world`.
+fn world_bounds((min, max): (Point3<f32>, Point3<f32>), world: Matrix4<f32>) -> (Point3<f32>, Point3<f32>){
+    let corner = |i: usize| world.transform_point(Point3::new(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z },
+    ));
+    (1..8).map(corner).fold((corner(0), corner(0)), |(min, max), p| (point_min(min, p), point_max(max, p)))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn translated(x: f32) -> Transform{
+        Transform{ translation: vec3(x, 0.0, 0.0), ..Transform::default() }
+    }
+
+    #[test]
+    fn children_follow_their_parent(){
+        let mut graph = SceneGraph::default();
+        let parent = graph.add(graph.root(), SceneNode::new("parent", translated(1.0)));
+        let child = graph.add(parent, SceneNode::new("child", translated(2.0)));
+        graph.update();
+
+        assert_eq!(graph.node(child).world_transform(), Matrix4::from_translation(vec3(3.0, 0.0, 0.0)));
+
+        graph.node_mut(parent).set_translation(vec3(5.0, 0.0, 0.0));
+        graph.update();
+
+        assert_eq!(graph.node(child).world_transform(), Matrix4::from_translation(vec3(7.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn clean_nodes_are_not_recomputed(){
+        let mut graph = SceneGraph::default();
+        let a = graph.add(graph.root(), SceneNode::new("a", translated(1.0)));
+        let b = graph.add(graph.root(), SceneNode::new("b", translated(2.0)));
+        graph.update();
+
+        // Poke the cached matrix; only dirty subtrees should be overwritten by update
+        graph.nodes[b].world = Matrix4::identity();
+        graph.node_mut(a).set_translation(vec3(4.0, 0.0, 0.0));
+        graph.update();
+
+        assert_eq!(graph.node(a).world_transform(), Matrix4::from_translation(vec3(4.0, 0.0, 0.0)));
+        assert_eq!(graph.node(b).world_transform(), Matrix4::identity());
+    }
+
+    #[test]
+    fn world_bounds_cover_every_corner(){
+        let transform = Transform{
+            translation: vec3(10.0, 0.0, 0.0),
+            rotation: Quaternion::from_angle_z(cgmath::Deg(90.0)),
+            scale: vec3(2.0, 1.0, 1.0),
+        };
+        let (min, max) = world_bounds((Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 3.0, 1.0)), transform.matrix());
+
+        // Scaled along x, then turned so model x runs along world y and model y against world x
+        assert!(min.distance(Point3::new(7.0, -2.0, -1.0)) < 1e-4, "{:?}", min);
+        assert!(max.distance(Point3::new(11.0, 2.0, 1.0)) < 1e-4, "{:?}", max);
+    }
+}
@@ -1,7 +1,6 @@
 use std::ffi::CString;
-use std::str;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::path::Path;
 use std::ptr;
 
 use gl::types::*;
@@ -9,25 +8,36 @@ use gl::types::*;
 use cgmath::prelude::*;
 use cgmath::Matrix4;
 
+/// Shader program compiled from source files. A `#include "file"` line in a source is replaced by that file's
+/// source, found relative to the including file, so stages can share code such as shaders/lighting.glsl.
 pub struct Shader{
     id: u32,
 }
 
+/// Reads a shader source, expanding its includes.
+fn readSource(path: &Path) -> String{
+    let source = fs::read_to_string(path).unwrap_or_else(|err| panic!("ERROR::SHADER::FILE_NOT_READ {}: {}", path.display(), err));
+    let mut res = String::with_capacity(source.len());
+    for line in source.lines(){
+        match line.trim().strip_prefix("#include"){
+            Some(include) => {
+                let include = path.with_file_name(include.trim().trim_matches('"'));
+                res.push_str(&readSource(&include));
+            },
+            None => res.push_str(line),
+        }
+        res.push('\n');
+    }
+    res
+}
+
 impl Shader{
     pub fn newGeometry(vertexPath: &str, fragmentPath: &str, geometryPath: &str) -> Shader{
         let mut res = Shader{ id:0 };
         
-        let mut vertexShaderSourceFile = File::open(vertexPath).unwrap();
-        let mut fragmentShaderSourceFile = File::open(fragmentPath).unwrap();
-        let mut geometryShaderSourceFile = File::open(geometryPath).unwrap();
-
-        let mut vertexShaderSource = String::new();
-        let mut fragmentShaderSource = String::new();
-        let mut geometryShaderSource = String::new();
-        
-        vertexShaderSourceFile.read_to_string(&mut vertexShaderSource).unwrap();
-        fragmentShaderSourceFile.read_to_string(&mut fragmentShaderSource).unwrap();
-        geometryShaderSourceFile.read_to_string(&mut geometryShaderSource).unwrap();
+        let vertexShaderSource = readSource(Path::new(vertexPath));
+        let fragmentShaderSource = readSource(Path::new(fragmentPath));
+        let geometryShaderSource = readSource(Path::new(geometryPath));
 
         let vertexShaderSource = CString::new(vertexShaderSource.as_bytes()).unwrap();
         let fragmentShaderSource = CString::new(fragmentShaderSource.as_bytes()).unwrap();
@@ -39,12 +49,12 @@ impl Shader{
             gl::CompileShader(vertexShader);
 
             let mut success = gl::FALSE as GLint;
-            let mut infoLog = Vec::with_capacity(512);
-            infoLog.set_len(512-1);
+            let mut infoLog = vec![0u8; 512];
+            let mut length = 0;
             gl::GetShaderiv(vertexShader, gl::COMPILE_STATUS, &mut success);
             if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(vertexShader, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                println!("{}", str::from_utf8_unchecked(&infoLog));
+                gl::GetShaderInfoLog(vertexShader, infoLog.len() as GLsizei, &mut length, infoLog.as_mut_ptr() as *mut GLchar);
+                println!("{}", String::from_utf8_lossy(&infoLog[..length as usize]));
             }
 
             let fragmentShader = gl::CreateShader(gl::FRAGMENT_SHADER);
@@ -53,8 +63,8 @@ impl Shader{
 
             gl::GetShaderiv(fragmentShader, gl::COMPILE_STATUS, &mut success);
             if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(fragmentShader, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                println!("{}", str::from_utf8_unchecked(&infoLog));
+                gl::GetShaderInfoLog(fragmentShader, infoLog.len() as GLsizei, &mut length, infoLog.as_mut_ptr() as *mut GLchar);
+                println!("{}", String::from_utf8_lossy(&infoLog[..length as usize]));
             }
 
             let geometryShader = gl::CreateShader(gl::GEOMETRY_SHADER);
@@ -63,8 +73,8 @@ impl Shader{
 
             gl::GetShaderiv(geometryShader, gl::COMPILE_STATUS, &mut success);
             if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(geometryShader, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                println!("{}", str::from_utf8_unchecked(&infoLog));
+                gl::GetShaderInfoLog(geometryShader, infoLog.len() as GLsizei, &mut length, infoLog.as_mut_ptr() as *mut GLchar);
+                println!("{}", String::from_utf8_lossy(&infoLog[..length as usize]));
             }
 
             let shaderProgram = gl::CreateProgram();
@@ -86,14 +96,8 @@ impl Shader{
     pub fn new(vertexPath: &str, fragmentPath: &str) -> Shader{
         let mut res = Shader{ id:0 };
 
-        let mut vertexShaderSourceFile = File::open(vertexPath).unwrap();
-        let mut fragmentShaderSourceFile = File::open(fragmentPath).unwrap();
-
-        let mut vertexShaderSource = String::new();
-        let mut fragmentShaderSource = String::new();
-        
-        vertexShaderSourceFile.read_to_string(&mut vertexShaderSource).unwrap();
-        fragmentShaderSourceFile.read_to_string(&mut fragmentShaderSource).unwrap();
+        let vertexShaderSource = readSource(Path::new(vertexPath));
+        let fragmentShaderSource = readSource(Path::new(fragmentPath));
 
         let vertexShaderSource = CString::new(vertexShaderSource.as_bytes()).unwrap();
         let fragmentShaderSource = CString::new(fragmentShaderSource.as_bytes()).unwrap();
@@ -104,12 +108,12 @@ impl Shader{
             gl::CompileShader(vertexShader);
 
             let mut success = gl::FALSE as GLint;
-            let mut infoLog = Vec::with_capacity(512);
-            infoLog.set_len(512-1);
+            let mut infoLog = vec![0u8; 512];
+            let mut length = 0;
             gl::GetShaderiv(vertexShader, gl::COMPILE_STATUS, &mut success);
             if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(vertexShader, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                println!("{}", str::from_utf8_unchecked(&infoLog));
+                gl::GetShaderInfoLog(vertexShader, infoLog.len() as GLsizei, &mut length, infoLog.as_mut_ptr() as *mut GLchar);
+                println!("{}", String::from_utf8_lossy(&infoLog[..length as usize]));
             }
 
             let fragmentShader = gl::CreateShader(gl::FRAGMENT_SHADER);
@@ -118,8 +122,8 @@ impl Shader{
 
             gl::GetShaderiv(fragmentShader, gl::COMPILE_STATUS, &mut success);
             if success != gl::TRUE as GLint {
-                gl::GetShaderInfoLog(fragmentShader, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                println!("{}", str::from_utf8_unchecked(&infoLog));
+                gl::GetShaderInfoLog(fragmentShader, infoLog.len() as GLsizei, &mut length, infoLog.as_mut_ptr() as *mut GLchar);
+                println!("{}", String::from_utf8_lossy(&infoLog[..length as usize]));
             }
 
             let shaderProgram = gl::CreateProgram();
@@ -156,6 +160,20 @@ impl Shader{
         }
     }
 
+    pub fn setUniform2f(&self, name: &str, vector: (f32, f32)){
+        let name = CString::new(name.as_bytes()).unwrap();
+        unsafe{
+            gl::Uniform2f(gl::GetUniformLocation(self.id, name.as_ptr()), vector.0, vector.1);
+        }
+    }
+
+    pub fn setUniform3i(&self, name: &str, vector: (i32, i32, i32)){
+        let name = CString::new(name.as_bytes()).unwrap();
+        unsafe{
+            gl::Uniform3i(gl::GetUniformLocation(self.id, name.as_ptr()), vector.0, vector.1, vector.2);
+        }
+    }
+
     pub fn setInt(&self, name: &str, value: i32){
         let name = CString::new(name.as_bytes()).unwrap();
         unsafe{
@@ -184,3 +202,26 @@ impl Shader{
         }
     }
 }
+
+impl Drop for Shader{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn includes_are_expanded(){
+        for path in &["shaders/shader.frag", "shaders/deferred.frag"]{
+            let source = readSource(Path::new(path));
+            assert!(source.starts_with("#version 330 core\n"));
+            assert!(!source.contains("#include"));
+            assert!(source.contains("float pointShadow("));
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use std::ptr;
+
+use cgmath::{Deg, Matrix4, Point3, Vector3, Vector4, ortho, perspective};
+use cgmath::prelude::*;
+use gl;
+use gl::types::*;
+
+use crate::camera::Camera;
+use crate::light::{Light, MAX_SPOT_LIGHTS};
+
+/// Most cascades a shadow map can have, the size of the cascade arrays in the lit shaders.
+pub const MAX_CASCADES: usize = 4;
+/// Blend between logarithmic splits, which match how perspective shrinks detail, and even splits, which keep
+/// the far cascades from getting too long.
+const SPLIT_LAMBDA: f32 = 0.75;
+/// How far beyond a cascade's bounds, towards the light, geometry still casts shadows into it.
+const CASTER_MARGIN: f32 = 50.0;
+
+/// Most point lights with shadows. Point lights after these, in scene order, cast none.
+pub const MAX_SHADOWED_POINT_LIGHTS: usize = 8;
+/// Distance from a point light beyond which it casts no shadows. Point shadow depths are stored divided by it.
+pub const POINT_SHADOW_FAR: f32 = 25.0;
+const POINT_SHADOW_NEAR: f32 = 0.05;
+
+/// Near plane of the spot light shadow projections. The lit shaders need it to linearise depths.
+pub const SPOT_SHADOW_NEAR: f32 = 0.1;
+/// Smallest tile the spot shadow atlas shrinks its tiles to when many lights share it.
+const MIN_SPOT_SHADOW_TILE: i32 = 128;
+
+/// View space distances where each of `count` cascades covering `near` to `far` ends, using the practical split
+/// scheme from "Parallel-Split Shadow Maps on Programmable GPUs" in GPU Gems 3.
+pub fn cascade_splits(near: f32, far: f32, count: usize) -> Vec<f32>{
+    (1..=count).map(|i| {
+        let t = i as f32 / count as f32;
+        let logarithmic = near * (far / near).powf(t);
+        let uniform = near + (far - near) * t;
+        SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * uniform
+    }).collect()
+}
+
+/// Orthographic light space transform covering the view volume of `view_projection`, a slice of the camera's
+/// view, for a light shining along `light_direction` into a `resolution` texel square shadow map.
+///
+/// The bounds are a sphere around the slice, so they keep their size as the camera turns, and they move in whole
+/// texels, so shadow edges don't shimmer as the camera moves.
+pub fn fit_cascade(view_projection: &Matrix4<f32>, light_direction: Vector3<f32>, resolution: i32) -> Matrix4<f32>{
+    let inverse = view_projection.invert().unwrap_or_else(Matrix4::identity);
+    let mut corners = vec!();
+    for &x in [-1.0, 1.0].iter(){
+        for &y in [-1.0, 1.0].iter(){
+            for &z in [-1.0, 1.0].iter(){
+                let corner = inverse * Vector4::new(x, y, z, 1.0);
+                corners.push(Point3::from_homogeneous(corner));
+            }
+        }
+    }
+    let centre = Point3::centroid(&corners);
+    // Rounded so float noise doesn't change the size from frame to frame
+    let radius = (corners.iter().map(|corner| corner.distance(centre)).fold(0.0, f32::max) * 16.0).ceil() / 16.0;
+
+    let direction = light_direction.normalize();
+    let up = if direction.x.abs() < 1e-3 && direction.z.abs() < 1e-3 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let rotation = Matrix4::look_at_dir(Point3::origin(), direction, up);
+
+    // Snapping moves the centre by up to a texel, so the bounds get one texel of padding
+    let texel = 2.0 * radius / (resolution - 2) as f32;
+    let extent = radius + texel;
+    let mut centre = rotation.transform_point(centre);
+    centre.x = (centre.x / texel).floor() * texel;
+    centre.y = (centre.y / texel).floor() * texel;
+
+    // Light space looks down -z, so the slice spans distances -centre.z +- radius
+    ortho(centre.x - extent, centre.x + extent, centre.y - extent, centre.y + extent, -centre.z - radius - CASTER_MARGIN, -centre.z + radius) * rotation
+}
+
+/// Projection times view of each cube face seen from the origin, in +x, -x, +y, -y, +z, -z order with the up
+/// vectors of OpenGL cube maps.
+pub fn cube_face_transforms() -> [Matrix4<f32>; 6]{
+    let projection = perspective(Deg(90.0), 1.0, POINT_SHADOW_NEAR, POINT_SHADOW_FAR);
+    let (x, y, z) = (Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z());
+    let faces = [(x, -y), (-x, -y), (y, z), (-y, -z), (z, -y), (-z, -y)];
+    let mut transforms = [Matrix4::identity(); 6];
+    for (transform, &(direction, up)) in transforms.iter_mut().zip(faces.iter()){
+        *transform = projection * Matrix4::look_at_dir(Point3::origin(), direction, up);
+    }
+    transforms
+}
+
+/// Square tiles of `tile_size` texels, in rows from the bottom left of a `atlas_size` texel atlas, for `count`
+/// lights. Tiles are halved, down to `MIN_SPOT_SHADOW_TILE`, until they all fit; lights that still don't fit
+/// get no tile.
+pub fn atlas_tiles(atlas_size: i32, tile_size: i32, count: usize) -> Vec<AtlasTile>{
+    let mut size = tile_size.min(atlas_size);
+    while size > MIN_SPOT_SHADOW_TILE && ((atlas_size / size) * (atlas_size / size)) < count as i32 {
+        size /= 2;
+    }
+
+    let columns = (atlas_size / size).max(1) as usize;
+    (0..count.min(columns * columns)).map(|i| AtlasTile{
+        x: (i % columns) as i32 * size,
+        y: (i / columns) as i32 * size,
+        size,
+    }).collect()
+}
+
+/// Perspective light space transform of a spot light at `position` shining along `direction`, wide enough for
+/// its `outer_cutoff` in degrees and reaching `far`.
+pub fn spot_transform(position: Vector3<f32>, direction: Vector3<f32>, outer_cutoff: f32, far: f32) -> Matrix4<f32>{
+    let direction = direction.normalize();
+    let up = if direction.x.abs() < 1e-3 && direction.z.abs() < 1e-3 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let fovy = Deg((2.0 * outer_cutoff).min(179.0));
+    perspective(fovy, 1.0, SPOT_SHADOW_NEAR, far) * Matrix4::look_at_dir(Point3::from_vec(position), direction, up)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasTile{
+    /// Bottom left corner in texels.
+    pub x: i32,
+    pub y: i32,
+    /// Width and height in texels.
+    pub size: i32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpotShadow{
+    /// Index of the light among the scene's spot lights, as packed into the `Lights` block.
+    pub light: usize,
+    /// World space to the light's clip space.
+    pub transform: Matrix4<f32>,
+    pub tile: AtlasTile,
+    /// The tile's offset and scale in texture coordinates, with w 1 to mark the light as shadowed.
+    pub rect: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cascade{
+    /// World space to the cascade's clip space.
+    pub transform: Matrix4<f32>,
+    /// View space distance where the cascade ends and the next one starts.
+    pub far: f32,
+}
+
+/// Shadow map for a directional light, split into cascades that each cover a slice of the view frustum.
+/// Near slices are short, so close shadows get many more texels than far ones. The cascades are the layers of
+/// one depth texture array.
+pub struct CascadedShadowMap{
+    /// `GL_TEXTURE_2D_ARRAY` with one depth layer per cascade, 1.0 outside its borders.
+    pub texture: u32,
+    /// Sampler object that makes the texture unit it is bound to compare depths and filter the results, for
+    /// reading `texture` through a `sampler2DArrayShadow`.
+    pub compare_sampler: u32,
+    /// Width and height of each cascade.
+    pub size: i32,
+    pub cascades: Vec<Cascade>,
+    fbo: u32,
+}
+
+impl CascadedShadowMap{
+    /// `count` is clamped to `MAX_CASCADES`.
+    pub fn new(size: i32, count: usize) -> CascadedShadowMap{
+        let count = count.clamp(1, MAX_CASCADES);
+        let mut map = CascadedShadowMap{
+            texture: 0,
+            compare_sampler: 0,
+            size,
+            cascades: vec![Cascade{ transform: Matrix4::identity(), far: 0.0 }; count],
+            fbo: 0,
+        };
+
+        unsafe {
+            gl::GenTextures(1, &mut map.texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, map.texture);
+            gl::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, gl::DEPTH_COMPONENT24 as i32, size, size, count as i32, 0, gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameterfv(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_BORDER_COLOR, &Vector4::<f32>{x:1.0, y:1.0, z:1.0, w:1.0} as *const Vector4<f32> as *const GLfloat);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+
+            gl::GenSamplers(1, &mut map.compare_sampler);
+            gl::SamplerParameteri(map.compare_sampler, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::SamplerParameteri(map.compare_sampler, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::SamplerParameteri(map.compare_sampler, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::SamplerParameteri(map.compare_sampler, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            gl::SamplerParameterfv(map.compare_sampler, gl::TEXTURE_BORDER_COLOR, &Vector4::<f32>{x:1.0, y:1.0, z:1.0, w:1.0} as *const Vector4<f32> as *const GLfloat);
+            gl::SamplerParameteri(map.compare_sampler, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as i32);
+            gl::SamplerParameteri(map.compare_sampler, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+
+            gl::GenFramebuffers(1, &mut map.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, map.fbo);
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, map.texture, 0, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                println!("ERROR::FRAMEBUFFER:: shadow cascade framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        map
+    }
+
+    /// Refits the cascades to the view of `camera`, covering its near to far planes.
+    pub fn update(&mut self, camera: &Camera, light_direction: Vector3<f32>){
+        let view = camera.get_view();
+        let splits = cascade_splits(camera.near, camera.far, self.cascades.len());
+        let mut start = camera.near;
+        for (cascade, &end) in self.cascades.iter_mut().zip(splits.iter()){
+            cascade.transform = fit_cascade(&(camera.projection_between(start, end) * view), light_direction, self.size);
+            cascade.far = end;
+            start = end;
+        }
+    }
+
+    /// Binds cascade `index` as the depth target, clears it and sets the viewport to cover it.
+    ///
+    /// # Safety
+    /// The OpenGL context the shadow map was created in must be current.
+    pub unsafe fn bind_cascade(&self, index: usize){
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, self.texture, 0, index as i32);
+        gl::Viewport(0, 0, self.size, self.size);
+        gl::Clear(gl::DEPTH_BUFFER_BIT);
+    }
+}
+
+impl Drop for CascadedShadowMap{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteSamplers(1, &self.compare_sampler);
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Omnidirectional shadows for up to `MAX_SHADOWED_POINT_LIGHTS` point lights, each rendered in one pass into
+/// the six faces of a cube by `shaders/point_shadow.geom`.
+///
+/// GL 3.3 has no cube map arrays, so the faces of light `i` are layers `6 * i` to `6 * i + 5` of one depth
+/// texture array and the lit shaders pick the face from the direction to the light. Depths are linear
+/// distances to the light over `POINT_SHADOW_FAR`.
+pub struct PointShadowMaps{
+    /// `GL_TEXTURE_2D_ARRAY` with six depth layers per light.
+    pub texture: u32,
+    /// Width and height of each face.
+    pub size: i32,
+    /// Number of lights drawn into the maps by the last render.
+    pub lights: usize,
+    fbo: u32,
+}
+
+impl PointShadowMaps{
+    pub fn new(size: i32) -> PointShadowMaps{
+        let mut maps = PointShadowMaps{ texture: 0, size, lights: 0, fbo: 0 };
+
+        unsafe {
+            gl::GenTextures(1, &mut maps.texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, maps.texture);
+            gl::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, gl::DEPTH_COMPONENT24 as i32, size, size, (6 * MAX_SHADOWED_POINT_LIGHTS) as i32, 0, gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+
+            // Attaching the whole array makes the framebuffer layered, so gl_Layer picks the face
+            gl::GenFramebuffers(1, &mut maps.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, maps.fbo);
+            gl::FramebufferTexture(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, maps.texture, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                println!("ERROR::FRAMEBUFFER:: point shadow framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        maps
+    }
+
+    /// Binds every face of every light as the depth target, clears them and sets the viewport to cover a face.
+    ///
+    /// # Safety
+    /// The OpenGL context the maps were created in must be current.
+    pub unsafe fn bind(&self){
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        gl::Viewport(0, 0, self.size, self.size);
+        gl::Clear(gl::DEPTH_BUFFER_BIT);
+    }
+}
+
+impl Drop for PointShadowMaps{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Perspective shadow maps for the shadow casting spot lights, each drawn into its own tile of one shared depth
+/// texture so they take a single texture unit however many lights there are.
+pub struct SpotShadowAtlas{
+    /// `GL_TEXTURE_2D` depth texture, 1.0 outside its borders.
+    pub texture: u32,
+    /// Width and height of the whole atlas.
+    pub size: i32,
+    /// Width and height lights get when the atlas has room for them.
+    pub tile_size: i32,
+    /// Lights drawn into the atlas by the last render.
+    pub shadows: Vec<SpotShadow>,
+    fbo: u32,
+}
+
+impl SpotShadowAtlas{
+    pub fn new(size: i32, tile_size: i32) -> SpotShadowAtlas{
+        let mut atlas = SpotShadowAtlas{ texture: 0, size, tile_size, shadows: vec!(), fbo: 0 };
+
+        unsafe {
+            gl::GenTextures(1, &mut atlas.texture);
+            gl::BindTexture(gl::TEXTURE_2D, atlas.texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT24 as i32, size, size, 0, gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, &Vector4::<f32>{x:1.0, y:1.0, z:1.0, w:1.0} as *const Vector4<f32> as *const GLfloat);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenFramebuffers(1, &mut atlas.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, atlas.fbo);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, atlas.texture, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                println!("ERROR::FRAMEBUFFER:: spot shadow atlas framebuffer is not complete!");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        atlas
+    }
+
+    /// Hands out tiles to the spot lights in `lights` that cast shadows and aims them from where the lights are
+    /// now, out to `far`.
+    pub fn update(&mut self, lights: &[Light], camera: &Camera, far: f32){
+        let casters: Vec<_> = lights.iter()
+            .filter_map(|light| match light{
+                Light::Spot(light) => Some(light),
+                _ => None,
+            })
+            .take(MAX_SPOT_LIGHTS)
+            .enumerate()
+            .filter(|(_, light)| light.cast_shadows)
+            .collect();
+        let tiles = atlas_tiles(self.size, self.tile_size, casters.len());
+        if tiles.len() < casters.len() {
+            println!("ERROR::SHADOW:: Spot shadow atlas is full, {} lights cast no shadows", casters.len() - tiles.len());
+        }
+
+        let size = self.size as f32;
+        self.shadows = casters.iter().zip(tiles).map(|(&(index, light), tile)| {
+            let (position, direction) = light.placement(camera);
+            SpotShadow{
+                light: index,
+                transform: spot_transform(position, direction, light.outer_cutoff, far),
+                tile,
+                rect: [tile.x as f32 / size, tile.y as f32 / size, tile.size as f32 / size, 1.0],
+            }
+        }).collect();
+    }
+
+    /// Binds the atlas as the depth target and clears it.
+    ///
+    /// # Safety
+    /// The OpenGL context the atlas was created in must be current.
+    pub unsafe fn bind(&self){
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        gl::Viewport(0, 0, self.size, self.size);
+        gl::Clear(gl::DEPTH_BUFFER_BIT);
+    }
+
+    /// Sets the viewport to cover the tile of shadow `index`.
+    ///
+    /// # Safety
+    /// The atlas must be bound, by `bind`, in the current OpenGL context.
+    pub unsafe fn bind_tile(&self, index: usize){
+        let tile = self.shadows[index].tile;
+        gl::Viewport(tile.x, tile.y, tile.size, tile.size);
+    }
+}
+
+impl Drop for SpotShadowAtlas{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use cgmath::vec3;
+
+    #[test]
+    fn splits_grow_with_distance_and_end_at_far(){
+        let splits = cascade_splits(0.1, 100.0, 4);
+
+        assert_eq!(splits.len(), 4);
+        assert!((splits[3] - 100.0).abs() < 1e-3);
+        assert!(splits.windows(2).all(|pair| pair[1] - pair[0] > 0.0));
+        // The first cascade is much shorter than an even split
+        assert!(splits[0] < 25.0);
+    }
+
+    #[test]
+    fn cascades_contain_their_frustum_slice(){
+        let view = Matrix4::look_at(Point3::new(3.0, 2.0, 5.0), Point3::new(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0));
+        let view_projection = perspective(Deg(45.0), 4.0 / 3.0, 1.0, 10.0) * view;
+        let transform = fit_cascade(&view_projection, vec3(1.0, -10.0, 0.0), 2048);
+
+        let inverse = view_projection.invert().unwrap();
+        for &x in [-1.0, 1.0].iter(){
+            for &y in [-1.0, 1.0].iter(){
+                for &z in [-1.0, 1.0].iter(){
+                    let corner = Point3::from_homogeneous(inverse * Vector4::new(x, y, z, 1.0));
+                    let clip = transform.transform_point(corner);
+                    assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0 && clip.z.abs() <= 1.0, "{:?} maps to {:?}", corner, clip);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn each_cube_face_sees_its_own_direction(){
+        let directions = [vec3(1.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, -1.0)];
+
+        for (transform, direction) in cube_face_transforms().iter().zip(directions.iter()){
+            let clip = transform.transform_point(Point3::from_vec(*direction * 2.0));
+            assert!(clip.x.abs() < 1e-5 && clip.y.abs() < 1e-5 && clip.z.abs() < 1.0, "{:?} maps to {:?}", direction, clip);
+        }
+    }
+
+    #[test]
+    fn atlas_tiles_shrink_to_fit_without_overlapping(){
+        assert_eq!(atlas_tiles(4096, 1024, 16).iter().filter(|tile| tile.size == 1024).count(), 16);
+
+        let tiles = atlas_tiles(4096, 1024, 17);
+        assert_eq!(tiles.len(), 17);
+        assert!(tiles.iter().all(|tile| tile.size == 512 && tile.x + tile.size <= 4096 && tile.y + tile.size <= 4096));
+        for (i, a) in tiles.iter().enumerate(){
+            for b in tiles[i + 1..].iter(){
+                assert!(a.x + a.size <= b.x || b.x + b.size <= a.x || a.y + a.size <= b.y || b.y + b.size <= a.y, "{:?} overlaps {:?}", a, b);
+            }
+        }
+
+        // Once tiles reach the minimum size, the lights that don't fit go without
+        assert_eq!(atlas_tiles(256, 256, 5).len(), 4);
+    }
+
+    #[test]
+    fn spot_transform_centres_the_light_direction(){
+        let transform = spot_transform(vec3(1.0, 2.0, 3.0), vec3(0.0, -1.0, -1.0), 30.0, 50.0);
+
+        let ahead = transform.transform_point(Point3::new(1.0, -3.0, -2.0));
+        assert!(ahead.x.abs() < 1e-5 && ahead.y.abs() < 1e-5 && ahead.z.abs() < 1.0, "{:?}", ahead);
+        let behind = transform * Vector4::new(1.0, 3.0, 4.0, 1.0);
+        assert!(behind.w < 0.0);
+    }
+}
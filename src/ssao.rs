@@ -0,0 +1,191 @@
+use std::os::raw::c_void;
+
+use cgmath::{Vector3, vec3};
+use cgmath::prelude::*;
+use gl;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::framebuffer::Framebuffer;
+use crate::scene::{Scene, SsaoSettings};
+use crate::shader::Shader;
+
+/// Size of the `samples` array in `shaders/ssao.frag`.
+pub const MAX_SSAO_SAMPLES: usize = 64;
+/// Width and height of the tiled random rotation texture, which the blur pass averages out.
+const NOISE_SIZE: usize = 4;
+
+/// Sample offsets in a unit hemisphere around +z, packed closer to the centre so nearby geometry counts most.
+/// Seeded, so the pattern is the same on every run.
+pub fn hemisphere_kernel(count: usize) -> Vec<Vector3<f32>>{
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..count).map(|i| {
+        let direction = vec3(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(0.0, 1.0)).normalize();
+        let t = i as f32 / count as f32;
+        let scale = 0.1 + 0.9 * t * t;
+        direction * rng.gen_range(0.0, 1.0) * scale
+    }).collect()
+}
+
+/// Screen space ambient occlusion, from world space positions and normals such as the deferred G-buffer's.
+/// The forward pipeline draws them in a prepass first.
+pub struct Ssao{
+    prepass_shader: Shader,
+    ssao_shader: Shader,
+    blur_shader: Shader,
+    /// World space positions and normals drawn by `render_forward`, only for the forward pipeline.
+    geometry: Option<Framebuffer>,
+    occlusion: Framebuffer,
+    blurred: Framebuffer,
+    noise_texture: u32,
+}
+
+impl Ssao{
+    /// `prepass` allocates the position and normal targets for `render_forward`.
+    pub fn new(width: i32, height: i32, prepass: bool) -> Ssao{
+        let ssao_shader = Shader::new("shaders/postpro.vert", "shaders/ssao.frag");
+        ssao_shader.bindUniformBlock("Matrices", 0);
+        ssao_shader.useProgram();
+        for (i, sample) in hemisphere_kernel(MAX_SSAO_SAMPLES).iter().enumerate(){
+            ssao_shader.setUniform3f(&format!("samples[{}]", i), (sample.x, sample.y, sample.z));
+        }
+
+        let prepass_shader = Shader::new("shaders/shader.vert", "shaders/ssao_prepass.frag");
+        prepass_shader.bindUniformBlock("Matrices", 0);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let noise: Vec<[f32; 3]> = (0..NOISE_SIZE * NOISE_SIZE)
+            .map(|_| [rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), 0.0])
+            .collect();
+        let mut noise_texture = 0;
+        unsafe {
+            gl::GenTextures(1, &mut noise_texture);
+            gl::BindTexture(gl::TEXTURE_2D, noise_texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGB16F as i32, NOISE_SIZE as i32, NOISE_SIZE as i32, 0, gl::RGB, gl::FLOAT, noise.as_ptr() as *const c_void);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        let (geometry, occlusion, blurred) = targets(width, height, prepass);
+        Ssao{
+            prepass_shader,
+            ssao_shader,
+            blur_shader: Shader::new("shaders/postpro.vert", "shaders/ssao_blur.frag"),
+            geometry,
+            occlusion,
+            blurred,
+            noise_texture,
+        }
+    }
+
+    /// Recreates the screen sized targets.
+    pub fn resize(&mut self, width: i32, height: i32){
+        let (geometry, occlusion, blurred) = targets(width, height, self.geometry.is_some());
+        self.geometry = geometry;
+        self.occlusion = occlusion;
+        self.blurred = blurred;
+    }
+
+    /// Draws the scene's models into the position and normal targets, then computes occlusion from them.
+    /// Does nothing without the targets allocated by `new`.
+    ///
+    /// # Safety
+    /// The OpenGL context the targets were created in must be current, with the `Matrices` block up to date.
+    /// `quad_vao` must hold a fullscreen quad laid out for `shaders/postpro.vert`.
+    pub unsafe fn render_forward(&self, scene: &Scene, quad_vao: u32, settings: &SsaoSettings){
+        let geometry = match &self.geometry{
+            Some(geometry) => geometry,
+            None => return,
+        };
+
+        geometry.bind();
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        gl::Disable(gl::BLEND);
+        self.prepass_shader.useProgram();
+        for (model, transform) in scene.graph.models(){
+            self.prepass_shader.setMat4("u_model", transform);
+            model.draw(&self.prepass_shader);
+        }
+        gl::Enable(gl::BLEND);
+
+        self.render(geometry.textures[0], geometry.textures[1], quad_vao, settings);
+    }
+
+    /// Computes blurred occlusion from world space position and normal textures, where a zero normal marks
+    /// pixels without geometry.
+    ///
+    /// # Safety
+    /// The OpenGL context the targets were created in must be current and `positions` and `normals` must be
+    /// textures in it. `quad_vao` must hold a fullscreen quad laid out for `shaders/postpro.vert`.
+    pub unsafe fn render(&self, positions: u32, normals: u32, quad_vao: u32, settings: &SsaoSettings){
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+        gl::BindVertexArray(quad_vao);
+
+        self.occlusion.bind();
+        let shader = &self.ssao_shader;
+        shader.useProgram();
+        shader.setInt("g_position", 0);
+        shader.setInt("g_normal", 1);
+        shader.setInt("noise", 2);
+        shader.setUniform2f("noise_scale", (self.occlusion.width as f32 / NOISE_SIZE as f32, self.occlusion.height as f32 / NOISE_SIZE as f32));
+        shader.setInt("sample_count", settings.samples.min(MAX_SSAO_SAMPLES) as i32);
+        shader.setFloat("radius", settings.radius);
+        shader.setFloat("bias", settings.bias);
+        for (unit, &texture) in [positions, normals, self.noise_texture].iter().enumerate(){
+            gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        self.blurred.bind();
+        self.blur_shader.useProgram();
+        self.blur_shader.setInt("occlusion", 0);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, self.occlusion.texture);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        gl::BindVertexArray(0);
+        gl::Enable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+
+    /// Ambient visibility in the red channel, 1 where nothing is occluded. Valid after `render`.
+    pub fn texture(&self) -> u32{
+        self.blurred.texture
+    }
+}
+
+fn targets(width: i32, height: i32, prepass: bool) -> (Option<Framebuffer>, Framebuffer, Framebuffer){
+    let geometry = if prepass { Some(Framebuffer::multiple(width, height, &[gl::RGB16F, gl::RGB16F])) } else { None };
+    (geometry, Framebuffer::new(width, height, gl::R8), Framebuffer::new(width, height, gl::R8))
+}
+
+impl Drop for Ssao{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteTextures(1, &self.noise_texture);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn kernel_fills_the_hemisphere_towards_the_centre(){
+        let kernel = hemisphere_kernel(MAX_SSAO_SAMPLES);
+
+        assert_eq!(kernel.len(), MAX_SSAO_SAMPLES);
+        assert!(kernel.iter().all(|s| s.z >= 0.0 && s.magnitude() <= 1.0));
+        // Early samples are scaled down the most
+        assert!(kernel[0].magnitude() <= 0.1);
+        assert_eq!(kernel, hemisphere_kernel(MAX_SSAO_SAMPLES));
+    }
+}
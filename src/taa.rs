@@ -0,0 +1,135 @@
+use cgmath::{Matrix4, vec3};
+use cgmath::prelude::*;
+use gl;
+
+use crate::framebuffer::Framebuffer;
+use crate::shader::Shader;
+
+/// Number of jitter offsets cycled through before the pattern repeats.
+const JITTER_SAMPLES: u32 = 8;
+/// Weight of the current frame in the blend, so the history averages roughly the last ten frames.
+const BLEND: f32 = 0.1;
+
+/// Element `index` of the Halton low discrepancy sequence in `base`, in [0, 1).
+pub fn halton(mut index: u32, base: u32) -> f32{
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Temporal anti-aliasing. Every frame is drawn with the projection shifted by a different sub-pixel offset,
+/// then blended into an HDR history reprojected from the previous frame's camera.
+///
+/// Reprojection only follows the camera, so moving objects lean on the neighbourhood clamp in `shaders/taa.frag`
+/// to avoid ghosting.
+pub struct TemporalAa{
+    shader: Shader,
+    /// Resolved frames, the current one and the history it was blended from, swapped every frame.
+    history: [Framebuffer; 2],
+    current: usize,
+    frame: u32,
+    /// Unjittered view-projection of the last resolved frame, `None` when there is no history.
+    previous_view_projection: Option<Matrix4<f32>>,
+}
+
+impl TemporalAa{
+    pub fn new(width: i32, height: i32) -> TemporalAa{
+        TemporalAa{
+            shader: Shader::new("shaders/postpro.vert", "shaders/taa.frag"),
+            history: history(width, height),
+            current: 0,
+            frame: 0,
+            previous_view_projection: None,
+        }
+    }
+
+    /// Recreates the history for a new screen size, starting over from the next frame.
+    pub fn resize(&mut self, width: i32, height: i32){
+        self.history = history(width, height);
+        self.previous_view_projection = None;
+    }
+
+    /// Offset of this frame's samples from the pixel centres, in pixels, within half a pixel either way.
+    pub fn jitter(&self) -> (f32, f32){
+        let index = self.frame % JITTER_SAMPLES + 1;
+        (halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+    }
+
+    /// `projection` shifted by this frame's jitter for a `width` by `height` target.
+    pub fn jittered(&self, projection: Matrix4<f32>, width: i32, height: i32) -> Matrix4<f32>{
+        let (x, y) = self.jitter();
+        Matrix4::from_translation(vec3(2.0 * x / width as f32, 2.0 * y / height as f32, 0.0)) * projection
+    }
+
+    /// Blends the HDR colour texture `scene` into the history and moves on to the next jitter offset.
+    /// `depth` is the scene's depth texture, drawn with `jittered_view_projection`; `view_projection` is the same
+    /// without jitter.
+    ///
+    /// # Safety
+    /// The OpenGL context the history was created in must be current and `scene` and `depth` must be textures
+    /// in it. `quad_vao` must hold a fullscreen quad laid out for `shaders/postpro.vert`.
+    pub unsafe fn resolve(&mut self, scene: u32, depth: u32, view_projection: Matrix4<f32>, jittered_view_projection: Matrix4<f32>, quad_vao: u32){
+        let previous = self.current;
+        self.current = 1 - self.current;
+
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+        gl::BindVertexArray(quad_vao);
+
+        self.history[self.current].bind();
+        let shader = &self.shader;
+        shader.useProgram();
+        shader.setInt("current_frame", 0);
+        shader.setInt("history", 1);
+        shader.setInt("depth", 2);
+        shader.setMat4("inverse_view_projection", jittered_view_projection.invert().unwrap_or_else(Matrix4::identity));
+        shader.setMat4("previous_view_projection", self.previous_view_projection.unwrap_or(view_projection));
+        shader.setInt("has_history", self.previous_view_projection.is_some() as i32);
+        shader.setFloat("blend", BLEND);
+        for (unit, &texture) in [scene, self.history[previous].texture, depth].iter().enumerate(){
+            gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindVertexArray(0);
+        gl::Enable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+
+        self.previous_view_projection = Some(view_projection);
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Anti-aliased HDR scene colour, valid after `resolve`.
+    pub fn texture(&self) -> u32{
+        self.history[self.current].texture
+    }
+}
+
+fn history(width: i32, height: i32) -> [Framebuffer; 2]{
+    [Framebuffer::new(width, height, gl::RGBA16F), Framebuffer::new(width, height, gl::RGBA16F)]
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn halton_subdivides_the_unit_interval(){
+        assert_eq!(halton(1, 2), 0.5);
+        assert_eq!(halton(2, 2), 0.25);
+        assert_eq!(halton(3, 2), 0.75);
+        assert!((halton(1, 3) - 1.0 / 3.0).abs() < 1e-6);
+        assert!((halton(4, 3) - 4.0 / 9.0).abs() < 1e-6);
+
+        let offsets: Vec<(f32, f32)> = (1..=JITTER_SAMPLES).map(|i| (halton(i, 2) - 0.5, halton(i, 3) - 0.5)).collect();
+        assert!(offsets.iter().all(|&(x, y)| x.abs() < 0.5 && y.abs() < 0.5));
+    }
+}
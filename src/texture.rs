@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::os::raw::c_void;
+use std::path::Path;
+use std::ptr;
+
+use gl;
+use gl::types::*;
+use image;
+use image::DynamicImage::*;
+use image::GenericImageView;
+use image::hdr::HDRDecoder;
+
+use crate::ibl::FaceTarget;
+use crate::shader::Shader;
+
+/// A cube map texture, such as a skybox.
+pub struct CubeMap{
+    pub id: u32,
+    /// Width and height of each face at the base level.
+    pub size: i32,
+}
+
+impl CubeMap{
+    /// Loads six images in +x, -x, +y, -y, +z, -z order.
+    pub fn from_faces(faces: &[String]) -> Result<CubeMap, String>{
+        let mut images = Vec::with_capacity(faces.len());
+        for path in faces{
+            let img = image::open(Path::new(path)).map_err(|err| format!("failed to load {}: {}", path, err))?;
+            // Colour faces are sRGB encoded, so they are decoded to linear when sampled
+            let (internal_format, format) = match img{
+                ImageLuma8(_) => (gl::RED, gl::RED),
+                ImageLumaA8(_) => (gl::RG, gl::RG),
+                ImageRgb8(_) => (gl::SRGB8, gl::RGB),
+                ImageRgba8(_) => (gl::SRGB8_ALPHA8, gl::RGBA),
+                _ => return Err(format!("unsupported image format in {}", path)),
+            };
+            images.push((img, internal_format, format));
+        }
+
+        let mut cube_map = CubeMap{ id: 0, size: images.first().map_or(0, |(img, _, _)| img.width() as i32) };
+
+        unsafe {
+            gl::GenTextures(1, &mut cube_map.id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, cube_map.id);
+
+            for (i, (img, internal_format, format)) in images.iter().enumerate(){
+                let data = img.raw_pixels();
+
+                gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32, 0, *internal_format as i32, img.width() as i32, img.height() as i32,
+                                0, *format, gl::UNSIGNED_BYTE, &data[0] as *const u8 as *const c_void);
+            }
+
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+
+        Ok(cube_map)
+    }
+
+    /// Loads an equirectangular Radiance `.hdr` panorama and projects it onto a floating point cube map with
+    /// `face_size` by `face_size` faces on the GPU.
+    pub fn from_equirectangular(path: &str, face_size: i32) -> Result<CubeMap, String>{
+        let file = File::open(path).map_err(|err| format!("failed to open {}: {}", path, err))?;
+        let decoder = HDRDecoder::new(BufReader::new(file)).map_err(|err| format!("failed to load {}: {}", path, err))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr().map_err(|err| format!("failed to load {}: {}", path, err))?;
+
+        let shader = Shader::new("shaders/ibl.vert", "shaders/equirectangular.frag");
+        let cube_map = CubeMap::empty(face_size, gl::RGB16F, 1);
+
+        unsafe {
+            let mut panorama = 0;
+            gl::GenTextures(1, &mut panorama);
+            gl::BindTexture(gl::TEXTURE_2D, panorama);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGB32F as i32, metadata.width as i32, metadata.height as i32,
+                            0, gl::RGB, gl::FLOAT, pixels.as_ptr() as *const c_void);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+            shader.useProgram();
+            shader.setInt("panorama", 0);
+            FaceTarget::new().draw_faces(&shader, &cube_map, 0);
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::DeleteTextures(1, &panorama);
+        }
+
+        Ok(cube_map)
+    }
+
+    /// Uninitialised floating point cube map to render into, with storage for `levels` mip levels.
+    /// Levels past the first are sampled trilinearly. A single level cube map can still have mipmaps generated.
+    pub fn empty(size: i32, internal_format: GLenum, levels: i32) -> CubeMap{
+        let mut cube_map = CubeMap{ id: 0, size };
+
+        unsafe {
+            gl::GenTextures(1, &mut cube_map.id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, cube_map.id);
+
+            for level in 0..levels{
+                let level_size = (size >> level).max(1);
+                for face in 0..6{
+                    gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, level, internal_format as i32, level_size, level_size,
+                                    0, gl::RGB, gl::FLOAT, ptr::null());
+                }
+            }
+
+            let min_filter = if levels > 1 { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            if levels > 1 {
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, levels - 1);
+            }
+
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+
+        cube_map
+    }
+}
+
+impl Drop for CubeMap{
+    fn drop(&mut self){
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
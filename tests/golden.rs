@@ -0,0 +1,184 @@
+//! Golden-image regression tests.
+//!
+//! Each test renders a canonical scene from `tests/scenes/` headlessly with its fixed camera and a fixed time,
+//! then compares the output with `tests/reference/<name>.png`. Failing renders and a diff image highlighting
+//! the pixels that changed are written to `target/golden/`.
+//!
+//! The rendering tests need EGL and an OpenGL 3.3 context, but no display, so they are ignored by default and run
+//! with `cargo test -- --include-ignored`. The references are rendered on Mesa's llvmpipe, which is also how CI runs
+//! them: `LIBGL_ALWAYS_SOFTWARE=1 cargo test -- --include-ignored`. A missing reference fails its test; set
+//! `ROPENGL_BLESS=1` to record missing or changed references after checking the new output by eye.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use image::{Rgb, RgbImage};
+
+/// Perceptual colour distance, from 0 to 1, above which a pixel counts as different.
+const PIXEL_THRESHOLD: f32 = 0.05;
+/// Fraction of pixels allowed to differ before the images are considered different.
+const MAX_DIFFERENT_PIXELS: f32 = 0.001;
+
+struct Comparison {
+    different_pixels: usize,
+    total_pixels: usize,
+    diff: RgbImage,
+}
+
+impl Comparison {
+    fn passed(&self) -> bool {
+        self.different_pixels as f32 <= self.total_pixels as f32 * MAX_DIFFERENT_PIXELS
+    }
+}
+
+/// Weighted euclidean distance in sRGB ("redmean"), a cheap approximation of perceived colour difference.
+fn distance(a: &Rgb<u8>, b: &Rgb<u8>) -> f32 {
+    let mean_r = (a[0] as f32 + b[0] as f32) / 2.0 / 255.0;
+    let dr = (a[0] as f32 - b[0] as f32) / 255.0;
+    let dg = (a[1] as f32 - b[1] as f32) / 255.0;
+    let db = (a[2] as f32 - b[2] as f32) / 255.0;
+
+    let d2 = (2.0 + mean_r) * dr * dr + 4.0 * dg * dg + (3.0 - mean_r) * db * db;
+    (d2 / 9.0).sqrt()
+}
+
+fn compare(reference: &RgbImage, candidate: &RgbImage) -> Comparison {
+    assert_eq!(reference.dimensions(), candidate.dimensions(), "image sizes differ");
+
+    let (width, height) = reference.dimensions();
+    let mut diff = RgbImage::new(width, height);
+    let mut different_pixels = 0;
+
+    for (x, y, expected) in reference.enumerate_pixels() {
+        let actual = candidate.get_pixel(x, y);
+
+        let pixel = if distance(expected, actual) > PIXEL_THRESHOLD {
+            different_pixels += 1;
+            Rgb([255, 0, 0])
+        } else {
+            let luma = (expected[0] as u32 * 3 + expected[1] as u32 * 6 + expected[2] as u32) / 10;
+            let faded = (luma / 3) as u8;
+            Rgb([faded, faded, faded])
+        };
+        diff.put_pixel(x, y, pixel);
+    }
+
+    Comparison {
+        different_pixels,
+        total_pixels: (width * height) as usize,
+        diff,
+    }
+}
+
+fn output_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("golden");
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Renders and captures a single headless frame. Shadows are drawn before the models that receive them and exposure
+/// starts at the scene's measured luminance, so the first frame is already the settled image.
+fn render(name: &str, scene: &str, extra_args: &[&str]) -> RgbImage {
+    let output = output_dir().join(format!("{}.png", name));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_ropengl"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["--headless", "--frames", "1", "--capture-frame", "0", "--time", "0"])
+        .args(["--scene", scene])
+        .args(extra_args)
+        .arg("--output").arg(&output)
+        .status()
+        .expect("failed to run renderer");
+    assert!(status.success(), "renderer exited with {}", status);
+
+    image::open(&output).expect("failed to open render").to_rgb()
+}
+
+fn check(name: &str, scene: &str) {
+    check_with(name, scene, &[]);
+}
+
+fn check_with(name: &str, scene: &str, extra_args: &[&str]) {
+    let candidate = render(name, scene, extra_args);
+    let reference_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("reference").join(format!("{}.png", name));
+    let bless = env::var_os("ROPENGL_BLESS").is_some();
+
+    if !reference_path.exists() || bless {
+        if bless {
+            fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+            candidate.save(&reference_path).unwrap();
+            return;
+        }
+        panic!(
+            "missing reference {}: check {} by eye, then rerun with ROPENGL_BLESS=1 to record it",
+            reference_path.display(), output_dir().join(format!("{}.png", name)).display()
+        );
+    }
+
+    let reference = image::open(&reference_path).expect("failed to open reference").to_rgb();
+    let comparison = compare(&reference, &candidate);
+
+    if !comparison.passed() {
+        let diff_path = output_dir().join(format!("{}.diff.png", name));
+        comparison.diff.save(&diff_path).unwrap();
+        panic!(
+            "{}: {} of {} pixels differ from {}, see {}",
+            name, comparison.different_pixels, comparison.total_pixels, reference_path.display(), diff_path.display()
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs EGL and an OpenGL 3.3 context"]
+fn corona() {
+    check("corona", "tests/scenes/corona.ron");
+}
+
+#[test]
+#[ignore = "needs EGL and an OpenGL 3.3 context"]
+fn cube_grid() {
+    check("cube_grid", "tests/scenes/cube_grid.ron");
+}
+
+#[test]
+#[ignore = "needs EGL and an OpenGL 3.3 context"]
+fn skybox() {
+    check("skybox", "tests/scenes/skybox.ron");
+}
+
+#[test]
+#[ignore = "needs EGL and an OpenGL 3.3 context"]
+fn full_scene() {
+    check("full_scene", "scenes/default.ron");
+}
+
+#[test]
+#[ignore = "needs EGL and an OpenGL 3.3 context"]
+fn full_scene_deferred() {
+    check_with("full_scene_deferred", "scenes/default.ron", &["--pipeline", "deferred"]);
+}
+
+#[test]
+fn comparison_ignores_small_differences() {
+    let reference = RgbImage::from_pixel(10, 10, Rgb([100, 150, 200]));
+    let mut candidate = reference.clone();
+    candidate.put_pixel(3, 3, Rgb([102, 151, 199]));
+
+    let comparison = compare(&reference, &candidate);
+    assert_eq!(comparison.different_pixels, 0);
+    assert!(comparison.passed());
+}
+
+#[test]
+fn comparison_marks_changed_pixels() {
+    let reference = RgbImage::from_pixel(10, 10, Rgb([100, 150, 200]));
+    let mut candidate = reference.clone();
+    candidate.put_pixel(3, 3, Rgb([255, 0, 0]));
+
+    let comparison = compare(&reference, &candidate);
+    assert_eq!(comparison.different_pixels, 1);
+    assert!(!comparison.passed());
+    assert_eq!(*comparison.diff.get_pixel(3, 3), Rgb([255, 0, 0]));
+}